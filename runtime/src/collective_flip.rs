@@ -0,0 +1,133 @@
+//! Randomness from the hashes of the last 81 blocks, mixed per subject.
+//!
+//! Every block author only controls one of the hashes, so no single author can pick the outcome.
+//! The value is still known to everyone once the block is built, so it is not fit for high stakes.
+
+use parity_codec::Encode;
+use rstd::prelude::*;
+use runtime_primitives::traits::{As, Hash};
+use safe_mix::TripletMix;
+use support::{decl_module, decl_storage, StorageValue};
+
+use crate::randomness::Randomness;
+
+/// Number of block hashes mixed together, a power of three for `TripletMix`
+const RANDOM_MATERIAL_LEN: u64 = 81;
+
+pub trait Trait: system::Trait {}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as RandomnessCollectiveFlip {
+        /// Hashes of the last blocks, overwritten in a ring
+        RandomMaterial get(random_material): Vec<T::Hash>;
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn on_initialize(n: T::BlockNumber) {
+            let parent_hash = <system::Module<T>>::parent_hash();
+            <RandomMaterial<T>>::mutate(|material| {
+                if (material.len() as u64) < RANDOM_MATERIAL_LEN {
+                    material.push(parent_hash);
+                } else {
+                    material[Self::material_index(n)] = parent_hash;
+                }
+            });
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// Ring position of the hash pushed in block `n`, the first block fills position zero
+    fn material_index(n: T::BlockNumber) -> usize {
+        (n.as_().saturating_sub(1) % RANDOM_MATERIAL_LEN) as usize
+    }
+}
+
+impl<T: Trait> Randomness<T::Hash> for Module<T> {
+    fn random(subject: &[u8]) -> T::Hash {
+        let index = Self::material_index(<system::Module<T>>::block_number());
+        let material = Self::random_material();
+
+        // Starting from the current ring position keeps the order of hashes stable between blocks
+        material.iter()
+            .cycle()
+            .skip(index)
+            .take(RANDOM_MATERIAL_LEN as usize)
+            .enumerate()
+            .map(|(i, hash)| (i as i8, subject, hash).using_encoded(T::Hashing::hash))
+            .triplet_mix()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::{Blake2Hasher, H256};
+    use runtime_io::with_externalities;
+    use runtime_primitives::{
+        testing::{Digest, DigestItem, Header},
+        traits::{BlakeTwo256, IdentityLookup, OnInitialize},
+        BuildStorage,
+    };
+    use support::impl_outer_origin;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl Trait for Test {}
+    type CollectiveFlip = Module<Test>;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        system::GenesisConfig::<Test>::default().build_storage().unwrap().0.into()
+    }
+
+    #[test]
+    fn keeps_the_last_81_hashes() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_eq!(CollectiveFlip::random(b"kitty"), H256::default());
+
+            let parent_hash = |n: u64| H256::from([n as u8; 32]);
+            for n in 1..200 {
+                system::Module::<Test>::initialize(&n, &parent_hash(n), &H256::default());
+                CollectiveFlip::on_initialize(n);
+            }
+
+            // The oldest hash is overwritten first, so only blocks 119 to 199 are left
+            let material = CollectiveFlip::random_material();
+            assert_eq!(material.len(), 81);
+            for n in 119..200 {
+                assert_eq!(material[((n - 1) % 81) as usize], parent_hash(n));
+            }
+            assert!(!material.contains(&parent_hash(118)));
+        });
+    }
+
+    #[test]
+    fn subjects_get_different_values() {
+        with_externalities(&mut new_test_ext(), || {
+            CollectiveFlip::on_initialize(1);
+            assert_ne!(CollectiveFlip::random(b"kitty"), CollectiveFlip::random(b"other kitty"));
+            assert_eq!(CollectiveFlip::random(b"kitty"), CollectiveFlip::random(b"kitty"));
+        });
+    }
+}
@@ -0,0 +1,221 @@
+use parity_codec::{Decode, Encode};
+use rstd::{cmp, marker::PhantomData};
+use runtime_io::blake2_128;
+use runtime_primitives::Permill;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use support::traits::Get;
+
+/// How a child's dna is made from the dna of its parents
+pub trait Genetics {
+    /// Combine the matron and sire dna, using `random` as the only source of chance
+    fn combine(matron: &[u8; 16], sire: &[u8; 16], random: [u8; 16]) -> [u8; 16];
+}
+
+/// Takes every bit from one parent or the other, as picked by the random selector.
+///
+/// This is how dna was combined before `Genetics` was pluggable.
+pub struct BitwiseMix;
+
+impl Genetics for BitwiseMix {
+    fn combine(matron: &[u8; 16], sire: &[u8; 16], random: [u8; 16]) -> [u8; 16] {
+        let mut dna = [0u8; 16];
+        for i in 0..dna.len() {
+            dna[i] = (random[i] & matron[i]) | (!random[i] & sire[i]);
+        }
+        dna
+    }
+}
+
+/// Treats every dna byte as a gene of two alleles, one in each nibble.
+///
+/// A child inherits one allele of each gene from each parent, and every gene mutates
+/// with a chance of `MutationRate`, replacing one of its alleles by a random one.
+/// Lower alleles are dominant, so high alleles only show when a kitty carries two of them.
+pub struct Mendelian<MutationRate>(PhantomData<MutationRate>);
+
+impl<MutationRate: Get<Permill>> Genetics for Mendelian<MutationRate> {
+    fn combine(matron: &[u8; 16], sire: &[u8; 16], random: [u8; 16]) -> [u8; 16] {
+        let mutation_threshold = MutationRate::get() * 1_000_000u64;
+
+        let mut dna = [0u8; 16];
+        for i in 0..dna.len() {
+            let entropy = (random, i as u8).using_encoded(blake2_128);
+
+            let mut alleles = [
+                inherited_allele(matron[i], entropy[0] & 1 == 1),
+                inherited_allele(sire[i], entropy[0] & 2 == 2),
+            ];
+
+            let roll = u32::from_le_bytes([entropy[1], entropy[2], entropy[3], entropy[4]]) % 1_000_000;
+            if u64::from(roll) < mutation_threshold {
+                alleles[(entropy[5] & 1) as usize] = entropy[6] & 0x0f;
+            }
+
+            dna[i] = (alleles[0] << 4) | alleles[1];
+        }
+        dna
+    }
+}
+
+fn inherited_allele(gene: u8, high: bool) -> u8 {
+    if high { gene >> 4 } else { gene & 0x0f }
+}
+
+/// The allele a gene shows, the dominant of its two
+pub fn expressed_allele(gene: u8) -> u8 {
+    cmp::min(gene >> 4, gene & 0x0f)
+}
+
+/// Highest alleles, only shown by kitties carrying two of them
+const RARE_ALLELE: u8 = 12;
+
+#[derive(Encode, Decode, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, Serialize, Deserialize))]
+pub enum Pattern {
+    Solid,
+    Tabby,
+    Spotted,
+    Tortoiseshell,
+    Calico,
+}
+
+#[derive(Encode, Decode, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, Serialize, Deserialize))]
+pub enum EyeShape {
+    Round,
+    Almond,
+    Sleepy,
+    Slit,
+}
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+/// What a kitty looks like, decoded from the expressed alleles of its dna
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq, Serialize, Deserialize))]
+pub struct KittyTraits {
+    /// Index into a palette of 16 colors
+    pub body_color: u8,
+    pub pattern: Pattern,
+    pub eye_shape: EyeShape,
+    /// Index into a palette of 16 colors
+    pub eye_color: u8,
+    /// Based on how many genes show a rare allele
+    pub rarity: Rarity,
+}
+
+impl KittyTraits {
+    pub fn from_dna(dna: &[u8; 16]) -> Self {
+        let pattern = match expressed_allele(dna[1]) {
+            0..=5 => Pattern::Solid,
+            6..=9 => Pattern::Tabby,
+            10..=12 => Pattern::Spotted,
+            13..=14 => Pattern::Tortoiseshell,
+            _ => Pattern::Calico,
+        };
+        let eye_shape = match expressed_allele(dna[2]) / 4 {
+            0 => EyeShape::Round,
+            1 => EyeShape::Almond,
+            2 => EyeShape::Sleepy,
+            _ => EyeShape::Slit,
+        };
+        let rarity = match dna.iter().filter(|gene| expressed_allele(**gene) >= RARE_ALLELE).count() {
+            0 => Rarity::Common,
+            1..=2 => Rarity::Uncommon,
+            3..=4 => Rarity::Rare,
+            _ => Rarity::Legendary,
+        };
+
+        KittyTraits {
+            body_color: expressed_allele(dna[0]),
+            pattern,
+            eye_shape,
+            eye_color: expressed_allele(dna[3]),
+            rarity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub struct NoMutation;
+    impl Get<Permill> for NoMutation {
+        fn get() -> Permill {
+            Permill::from_percent(0)
+        }
+    }
+
+    pub struct AlwaysMutate;
+    impl Get<Permill> for AlwaysMutate {
+        fn get() -> Permill {
+            Permill::from_percent(100)
+        }
+    }
+
+    #[test]
+    fn bitwise_mix_picks_bits_by_selector() {
+        let dna = BitwiseMix::combine(&[0b1111_0000; 16], &[0b0011_0011; 16], [0b1010_1010; 16]);
+        assert_eq!(dna, [0b1011_0001; 16]);
+    }
+
+    #[test]
+    fn mendelian_inherits_an_allele_from_each_parent() {
+        let matron = [0x12; 16];
+        let sire = [0x34; 16];
+        for seed in 0..32u8 {
+            let dna = Mendelian::<NoMutation>::combine(&matron, &sire, [seed; 16]);
+            for gene in dna.iter() {
+                assert!(gene >> 4 == 1 || gene >> 4 == 2);
+                assert!(gene & 0x0f == 3 || gene & 0x0f == 4);
+            }
+        }
+    }
+
+    #[test]
+    fn mendelian_mutations_bring_new_alleles() {
+        let parent = [0x11; 16];
+        assert_eq!(Mendelian::<NoMutation>::combine(&parent, &parent, [7; 16]), parent);
+
+        let mutated = (0..32u8)
+            .map(|seed| Mendelian::<AlwaysMutate>::combine(&parent, &parent, [seed; 16]))
+            .any(|dna| dna != parent);
+        assert!(mutated);
+    }
+
+    #[test]
+    fn lower_alleles_are_dominant() {
+        assert_eq!(expressed_allele(0x3a), 3);
+        assert_eq!(expressed_allele(0xa3), 3);
+        assert_eq!(expressed_allele(0xbb), 11);
+    }
+
+    #[test]
+    fn traits_are_decoded_from_expressed_alleles() {
+        let mut dna = [0x0f; 16];
+        dna[0] = 0x7a;
+        dna[1] = 0xbb;
+        dna[2] = 0xf9;
+        dna[3] = 0x44;
+        dna[4] = 0xcc;
+        dna[5] = 0xfd;
+
+        assert_eq!(KittyTraits::from_dna(&dna), KittyTraits {
+            body_color: 7,
+            pattern: Pattern::Spotted,
+            eye_shape: EyeShape::Sleepy,
+            eye_color: 4,
+            rarity: Rarity::Uncommon,
+        });
+        assert_eq!(KittyTraits::from_dna(&[0xff; 16]).rarity, Rarity::Legendary);
+    }
+}
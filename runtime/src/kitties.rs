@@ -1,40 +1,233 @@
 use parity_codec::{Decode, Encode, Input, Output};
-use rstd::result;
+use rstd::{cmp, prelude::*, result};
 use runtime_io::blake2_128;
-use runtime_primitives::traits::{Bounded, Member, One, SimpleArithmetic};
+use runtime_primitives::Permill;
+use runtime_primitives::traits::{As, Bounded, Hash, Member, One, SimpleArithmetic, Zero};
 use support::{
-    decl_event, decl_module, decl_storage, dispatch::Result, ensure, traits::Currency, Parameter,
-    StorageMap, StorageValue,
+    decl_event, decl_module, decl_storage, dispatch::Result, ensure, Parameter, StorageMap, StorageValue,
+    traits::{Currency, ExistenceRequirement, Get, Imbalance, OnUnbalanced, ReservableCurrency, WithdrawReason},
 };
-use system::ensure_signed;
+use system::{ensure_root, ensure_signed};
+use crate::genetics::{Genetics, KittyTraits};
 use crate::linked_item::{LinkedList, LinkedItem};
+use crate::randomness::Randomness;
 
 pub trait Trait: system::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
     type KittyIndex: Parameter + Member + Default + SimpleArithmetic + Bounded + Copy;
-    type Currency: Currency<Self::AccountId>;
+    type Currency: ReservableCurrency<Self::AccountId>;
+    /// Blocks a kitty must rest after breeding, doubled for every two generations and every breeding
+    type BreedingCooldown: Get<Self::BlockNumber>;
+    /// Blocks between breeding and birth. Zero means the child is born right away
+    type GestationPeriod: Get<Self::BlockNumber>;
+    /// Part of every sale price taken by the market
+    type MarketFee: Get<Permill>;
+    /// Handler for the market fees
+    type MarketFeeDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+    /// Part of a sale price paid to the breeder of the kitty, unless the breeder is selling
+    type BreederRoyalty: Get<Permill>;
+    /// Fee to create a kitty
+    type CreationFee: Get<BalanceOf<Self>>;
+    /// Fee to breed kitties
+    type BreedingFee: Get<BalanceOf<Self>>;
+    /// Handler for the creation and breeding fees
+    type MintingFeeDestination: OnUnbalanced<NegativeImbalanceOf<Self>>;
+    /// Most kitties an account can own, if limited
+    type MaxKittiesPerAccount: Get<Option<u32>>;
+    /// How the dna of a child is made from its parents
+    type Genetics: Genetics;
+    /// Generations of ancestry two kitties must not share to breed.
+    /// One rejects parents with their children and siblings, zero allows any pairing
+    type InbreedingDepth: Get<u32>;
+    /// Times a kitty can breed before it retires, unless overridden by `set_max_breeds`
+    type MaxBreedsPerKitty: Get<u32>;
+    /// Blocks after a mint commitment whose hash is mixed with the revealed secret, and blocks after that
+    /// to reveal it before the mint is forfeited. A committed pregnancy is due after at least `GestationPeriod`.
+    /// Must be well below the number of block hashes kept by `system`
+    type RevealDelay: Get<Self::BlockNumber>;
+    /// Kitties a storage migration goes through per block
+    type MigrationBatchSize: Get<u32>;
+    /// Smallest amount an offer can reserve
+    type MinimumOffer: Get<BalanceOf<Self>>;
+    /// Most offers a kitty can have at once, and most proposed swaps it can be part of
+    type MaxOffersPerKitty: Get<u32>;
+    /// Most auction ends, offer expiries and births scheduled for one block, each.
+    /// Births past the limit are not scheduled and wait for `give_birth`
+    type MaxScheduledPerBlock: Get<u32>;
+    /// Source of randomness for the dna of created kitties and the genes children get from each parent
+    type Randomness: Randomness<Self::Hash>;
 }
 
 type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
+type NegativeImbalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::NegativeImbalance;
 
-// pub struct Kitty(pub [u8; 16]);
-pub struct Kitty {
-    dna: [u8; 16]
+/// Maximum length of a kitty name in bytes
+pub const MAX_NAME_LENGTH: usize = 32;
+
+#[derive(Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Kitty<KittyIndex, BlockNumber> {
+    pub dna: [u8; 16],
+    /// 0 for created kitties, otherwise one more than the oldest parent generation
+    pub generation: u32,
+    pub matron: Option<KittyIndex>,
+    pub sire: Option<KittyIndex>,
+    pub birth_block: BlockNumber,
+    pub name: Vec<u8>,
+}
+
+pub type KittyOf<T> = Kitty<<T as Trait>::KittyIndex, <T as system::Trait>::BlockNumber>;
+
+/// Breeding state of a kitty
+#[derive(Encode, Decode, Default, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Cooldown<BlockNumber> {
+    /// First block the kitty can breed again
+    pub ready_at: BlockNumber,
+    /// Number of times the kitty has bred
+    pub breed_count: u32,
+}
+
+/// A pending birth, keyed by the matron
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Pregnancy<KittyIndex, BlockNumber> {
+    pub sire: KittyIndex,
+    /// Sire genes are kept in case the sire is gone by the time of birth
+    pub sire_dna: [u8; 16],
+    pub sire_generation: u32,
+    /// Block in which the child is born
+    pub due: BlockNumber,
+}
+
+/// A sale where the price decays linearly from `start_price` to `end_price` over `duration` blocks
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct DutchAuction<AccountId, Balance, BlockNumber> {
+    pub seller: AccountId,
+    pub start_price: Balance,
+    pub end_price: Balance,
+    pub start_block: BlockNumber,
+    pub duration: BlockNumber,
+}
+
+type DutchAuctionOf<T> = DutchAuction<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+/// An ascending bid auction. The highest bid is reserved until the auction is settled
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+    pub seller: AccountId,
+    /// Lowest accepted bid
+    pub reserve: Balance,
+    /// Block in which the auction is settled
+    pub end_block: BlockNumber,
+    /// (bidder, amount)
+    pub highest_bid: Option<(AccountId, Balance)>,
+}
+
+type AuctionOf<T> = Auction<<T as system::Trait>::AccountId, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+/// An offer to buy a kitty. The amount is reserved until the offer is accepted, withdrawn or expired
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Offer<Balance, BlockNumber> {
+    pub amount: Balance,
+    /// First block in which the offer can no longer be accepted
+    pub expires_at: BlockNumber,
+}
+
+pub type SwapId = u64;
+
+/// A proposal to exchange `offered` for `wanted`, with an optional payment from the proposer on top
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Swap<AccountId, KittyIndex, Balance, BlockNumber> {
+    pub proposer: AccountId,
+    pub offered: KittyIndex,
+    pub wanted: KittyIndex,
+    pub top_up: Option<Balance>,
+    /// First block in which the swap can no longer be accepted
+    pub expires_at: BlockNumber,
+}
+
+type SwapOf<T> = Swap<<T as system::Trait>::AccountId, <T as Trait>::KittyIndex, BalanceOf<T>, <T as system::Trait>::BlockNumber>;
+
+pub type BundleId = u64;
+
+/// Maximum number of kitties sold in one bundle
+pub const MAX_BUNDLE_SIZE: usize = 16;
+
+/// Several kitties sold together for one price. The kitties are locked until sold or cancelled
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct Bundle<AccountId, KittyIndex, Balance> {
+    pub seller: AccountId,
+    pub kitties: Vec<KittyIndex>,
+    pub price: Balance,
+}
+
+type BundleOf<T> = Bundle<<T as system::Trait>::AccountId, <T as Trait>::KittyIndex, BalanceOf<T>>;
+
+/// A kitty paid for with a hidden secret, minted once the secret is revealed
+#[derive(Encode, Decode, Clone)]
+#[cfg_attr(feature = "std", derive(Debug, PartialEq, Eq))]
+pub struct MintCommitment<KittyIndex, BlockNumber> {
+    /// Block whose hash is mixed with the secret. The secret can be revealed in any later block
+    pub reveal_after: BlockNumber,
+    /// Matron of a bred kitty, whose pregnancy is in `Pregnancies`. `None` for a created kitty
+    pub matron: Option<KittyIndex>,
+}
+
+type MintCommitmentOf<T> = MintCommitment<<T as Trait>::KittyIndex, <T as system::Trait>::BlockNumber>;
+
+impl<KittyIndex, BlockNumber: Default> Kitty<KittyIndex, BlockNumber> {
+    /// A kitty as stored before metadata was added, which only had the dna
+    fn legacy(dna: [u8; 16]) -> Self {
+        Kitty {
+            dna,
+            generation: 0,
+            matron: None,
+            sire: None,
+            birth_block: Default::default(),
+            name: Vec::new(),
+        }
+    }
 }
 
-impl Encode for Kitty {
-    fn encode_to<T: Output>(&self, output: &mut T) {
+impl<KittyIndex: Encode, BlockNumber: Encode> Encode for Kitty<KittyIndex, BlockNumber> {
+    fn encode_to<O: Output>(&self, output: &mut O) {
         for i in 0..16 { output.push_byte(self.dna[i]) }
+        self.generation.encode_to(output);
+        self.matron.encode_to(output);
+        self.sire.encode_to(output);
+        self.birth_block.encode_to(output);
+        self.name.encode_to(output);
     }
 }
 
-impl Decode for Kitty {
+impl<KittyIndex: Decode, BlockNumber: Decode + Default> Decode for Kitty<KittyIndex, BlockNumber> {
     fn decode<I: Input>(input: &mut I) -> Option<Self> {
         let mut dna = [0u8; 16];
         for i in 0..16 {
             dna[i] = input.read_byte()?;
         }
-        Some(Kitty{dna})
+        // Legacy kitties are exactly the 16 dna bytes, see `Module::migrate`.
+        // Anything longer must be a full kitty, so truncated entries fail to decode.
+        let mut generation = [0u8; 4];
+        match input.read(&mut generation) {
+            0 => return Some(Kitty::legacy(dna)),
+            4 => (),
+            _ => return None,
+        }
+        Some(Kitty {
+            dna,
+            generation: u32::from_le_bytes(generation),
+            matron: Decode::decode(input)?,
+            sire: Decode::decode(input)?,
+            birth_block: Decode::decode(input)?,
+            name: Decode::decode(input)?,
+        })
     }
 }
 
@@ -44,21 +237,96 @@ type OwnedKittiesList<T> = LinkedList<OwnedKitties<T>, <T as system::Trait>::Acc
 decl_storage! {
     trait Store for Module<T: Trait> as Kitties {
         /// Stores all the kitties, key is the kitty id / index
-        pub Kitties get(kitty): map T::KittyIndex => Option<Kitty>;
+        pub Kitties get(kitty): map T::KittyIndex => Option<KittyOf<T>>;
         /// Stores the total number of kitties. i.e. the next kitty index
         pub KittiesCount get(kitties_count): T::KittyIndex;
         /// Get kitty ownership. Stored in a linked map.
         pub OwnedKitties get(owned_kitties): map (T::AccountId, Option<T::KittyIndex>) =>
         Option<KittyLinkedItem<T>>;
+        /// Number of kitties owned by an account, kept in step with `OwnedKitties`
+        pub OwnedKittiesCount get(owned_kitties_count): map T::AccountId => u32;
 
         pub KittyOwners get(kitty_owner): map T::KittyIndex => Option<T::AccountId>;
         pub KittyPrices get(kitty_price): map T::KittyIndex => Option<BalanceOf<T>>;
+        /// Account allowed to breed with a sire it doesn't own. Consumed on use, cleared on transfer.
+        pub SiringApprovals get(siring_approval): map T::KittyIndex => Option<T::AccountId>;
+        /// Fee to breed with a sire listed for rent
+        pub SiringPrices get(siring_price): map T::KittyIndex => Option<BalanceOf<T>>;
+        /// Breeding cooldown of each kitty
+        pub KittyCooldowns get(kitty_cooldown): map T::KittyIndex => Cooldown<T::BlockNumber>;
+        /// Pending births, key is the matron id
+        pub Pregnancies get(pregnancy): map T::KittyIndex => Option<Pregnancy<T::KittyIndex, T::BlockNumber>>;
+        /// Matrons giving birth in a block
+        pub PregnanciesDue get(pregnancies_due): map T::BlockNumber => Vec<T::KittyIndex>;
+        /// Running dutch auctions. A kitty can not be transferred while listed
+        pub DutchAuctions get(dutch_auction): map T::KittyIndex => Option<DutchAuctionOf<T>>;
+        /// Running ascending bid auctions. A kitty can not be transferred while listed
+        pub Auctions get(auction): map T::KittyIndex => Option<AuctionOf<T>>;
+        /// Auctions settled in a block
+        pub AuctionsEnding get(auctions_ending): map T::BlockNumber => Vec<T::KittyIndex>;
+        /// Offers to buy a kitty, key is (kitty_id, bidder)
+        pub Offers get(offer): map (T::KittyIndex, T::AccountId) => Option<Offer<BalanceOf<T>, T::BlockNumber>>;
+        /// Accounts with an offer on a kitty
+        pub KittyOfferers get(kitty_offerers): map T::KittyIndex => Vec<T::AccountId>;
+        /// Offers expiring in a block. (kitty_id, bidder)
+        pub OffersExpiring get(offers_expiring): map T::BlockNumber => Vec<(T::KittyIndex, T::AccountId)>;
+        /// Account allowed to transfer a kitty on behalf of its owner. Cleared on every ownership change
+        pub KittyApprovals get(kitty_approval): map T::KittyIndex => Option<T::AccountId>;
+        /// Whether an operator can manage all kitties of an owner, key is (owner, operator)
+        pub OperatorApprovals get(operator_approval): map (T::AccountId, T::AccountId) => bool;
+        /// Proposed kitty swaps
+        pub Swaps get(swap): map SwapId => Option<SwapOf<T>>;
+        /// Id of the next proposed swap
+        pub NextSwapId get(next_swap_id): SwapId;
+        /// Proposed swaps a kitty is offered or wanted in
+        pub KittySwaps get(kitty_swaps): map T::KittyIndex => Vec<SwapId>;
+        /// Kitties for sale as one lot
+        pub Bundles get(bundle): map BundleId => Option<BundleOf<T>>;
+        /// Id of the next bundle
+        pub NextBundleId get(next_bundle_id): BundleId;
+        /// Bundle a kitty is listed in
+        pub KittyBundles get(kitty_bundle): map T::KittyIndex => Option<BundleId>;
+        /// Account that created or bred a kitty, paid a royalty on every resale
+        pub KittyBreeders get(kitty_breeder): map T::KittyIndex => Option<T::AccountId>;
+        /// Matron and sire of every bred kitty, kept after burning so lineage stays known
+        pub KittyParents get(kitty_parents): map T::KittyIndex => Option<(T::KittyIndex, T::KittyIndex)>;
+        /// Replaces `MaxBreedsPerKitty` when set
+        pub MaxBreedsOverride get(max_breeds_override): Option<u32>;
+        /// Pending mints, keyed by the account and the hash of its secret
+        pub MintCommitments get(mint_commitment): map (T::AccountId, T::Hash) => Option<MintCommitmentOf<T>>;
+        /// Commitment a pregnancy waits for, kept with the matron
+        pub CommittedPregnancies get(committed_pregnancy): map T::KittyIndex => Option<(T::AccountId, T::Hash)>;
+        /// Only allow minting through `commit_mint`, `commit_breed` and `reveal_mint`
+        pub CommitRevealOnly get(commit_reveal_only): bool;
+
+        /// Version of the storage layout, used to run migrations once
+        StorageVersion get(storage_version): u32;
+        /// Next kitty the running migration goes through
+        MigrationCursor get(migration_cursor): T::KittyIndex;
+        /// Kitties changed by the running migration so far
+        MigratedKitties get(migrated_kitties): u32;
+    }
+    add_extra_genesis {
+        /// Kitties to create at genesis. (owner, dna)
+        config(kitties): Vec<(T::AccountId, [u8; 16])>;
+
+        build(|storage: &mut runtime_primitives::StorageOverlay, _: &mut runtime_primitives::ChildrenStorageOverlay, config: &GenesisConfig<T>| {
+            runtime_io::with_storage(storage, || {
+                for &(ref owner, dna) in &config.kitties {
+                    let kitty_id = <Module<T>>::next_kitty_id().expect("Too many genesis kitties");
+                    <Module<T>>::insert_kitty(owner, kitty_id, Kitty::legacy(dna));
+                }
+                <StorageVersion<T>>::put(CURRENT_STORAGE_VERSION);
+            });
+        });
     }
 }
 
 decl_event!(
 	pub enum Event<T> where
 		<T as system::Trait>::AccountId,
+		<T as system::Trait>::BlockNumber,
+		<T as system::Trait>::Hash,
 		<T as Trait>::KittyIndex,
 		Balance = BalanceOf<T>,
 	{
@@ -68,8 +336,71 @@ decl_event!(
 		Transferred(AccountId, AccountId, KittyIndex),
 		/// A kitty is available for sale. (owner, kitty_id, price)
 		Ask(AccountId, KittyIndex, Option<Balance>),
-		/// A kitty is sold. (from, to, kitty_id, price)
-		Sold(AccountId, AccountId, KittyIndex, Balance),
+		/// A kitty is sold. (from, to, kitty_id, price, market fee, breeder royalty)
+		Sold(AccountId, AccountId, KittyIndex, Balance, Balance, Balance),
+		/// A kitty is burned. (owner, kitty_id)
+		Burned(AccountId, KittyIndex),
+		/// An account is allowed to breed with a sire. (owner, sire_id, account)
+		SiringApproved(AccountId, KittyIndex, AccountId),
+		/// A kitty is available for rent as a sire. (owner, sire_id, fee)
+		SireListed(AccountId, KittyIndex, Option<Balance>),
+		/// A sire is rented. (renter, sire owner, sire_id, fee)
+		SireRented(AccountId, AccountId, KittyIndex, Balance),
+		/// A kitty is renamed. (owner, kitty_id)
+		Renamed(AccountId, KittyIndex),
+		/// Stored kitties are upgraded to the current format. (number of kitties)
+		KittiesMigrated(u32),
+		/// Prices asked before transfers cleared them are removed. (number of prices)
+		PricesPurged(u32),
+		/// A price asked before transfers cleared prices is removed, its owner has to ask again. (kitty_id)
+		PriceCleared(KittyIndex),
+		/// Owned kitties are counted for every account. (number of kitties)
+		OwnedKittiesCounted(u32),
+		/// Parents of existing kitties are recorded. (number of kitties)
+		ParentsRecorded(u32),
+		/// A matron is pregnant. (owner, matron_id, sire_id, due block)
+		Conceived(AccountId, KittyIndex, KittyIndex, BlockNumber),
+		/// A kitty is born. (owner, kitty_id, matron_id)
+		Born(AccountId, KittyIndex, KittyIndex),
+		/// A dutch auction is started. (owner, kitty_id, start_price, end_price, duration)
+		DutchAuctionCreated(AccountId, KittyIndex, Balance, Balance, BlockNumber),
+		/// A dutch auction is cancelled. (owner, kitty_id)
+		DutchAuctionCancelled(AccountId, KittyIndex),
+		/// An auction is started. (owner, kitty_id, reserve, end block)
+		AuctionStarted(AccountId, KittyIndex, Balance, BlockNumber),
+		/// A bid is placed in an auction. (bidder, kitty_id, amount)
+		Bid(AccountId, KittyIndex, Balance),
+		/// An auction ended without bids. (owner, kitty_id)
+		AuctionEnded(AccountId, KittyIndex),
+		/// An auction ended without a sale, as the winner owns too many kitties by now or the sale can not be paid.
+		/// The winning bid is released. (owner, bidder, kitty_id, bid)
+		AuctionFailed(AccountId, AccountId, KittyIndex, Balance),
+		/// An offer is made for a kitty. (bidder, kitty_id, amount, expires at)
+		OfferMade(AccountId, KittyIndex, Balance, BlockNumber),
+		/// An offer is withdrawn or expired. (bidder, kitty_id)
+		OfferRemoved(AccountId, KittyIndex),
+		/// An account is approved to transfer a kitty. (owner, approved, kitty_id)
+		Approval(AccountId, Option<AccountId>, KittyIndex),
+		/// An operator is allowed or disallowed to manage all kitties of an owner. (owner, operator, approved)
+		ApprovalForAll(AccountId, AccountId, bool),
+		/// A swap is proposed. (proposer, swap_id, offered kitty_id, wanted kitty_id)
+		SwapProposed(AccountId, SwapId, KittyIndex, KittyIndex),
+		/// A swap is accepted and both kitties exchanged. (proposer, acceptor, swap_id)
+		Swapped(AccountId, AccountId, SwapId),
+		/// A swap is cancelled. (swap_id)
+		SwapCancelled(SwapId),
+		/// Kitties are available for sale as one lot. (owner, bundle_id, price)
+		BundleAsk(AccountId, BundleId, Balance),
+		/// A bundle is sold. (from, to, bundle_id, price, market fee, breeder royalties)
+		BundleSold(AccountId, AccountId, BundleId, Balance, Balance, Balance),
+		/// A bundle is cancelled. (bundle_id)
+		BundleCancelled(BundleId),
+		/// A kitty reached the breeding limit and can not breed anymore. (kitty_id)
+		Retired(KittyIndex),
+		/// A kitty is paid for with a hidden secret. (owner, secret hash, reveal after block)
+		MintCommitted(AccountId, Hash, BlockNumber),
+		/// A secret is not revealed in time and its kitty is never minted. (owner, secret hash)
+		MintForfeited(AccountId, Hash),
 	}
 );
 
@@ -77,27 +408,162 @@ decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event<T>() = default;
 
+        fn on_initialize() {
+            Self::migrate();
+        }
+
+        fn on_finalize(n: T::BlockNumber) {
+            for matron_id in <PregnanciesDue<T>>::take(n) {
+                // Births that can not happen now are left for `give_birth`
+                let _ = Self::do_give_birth(matron_id);
+            }
+            for kitty_id in <AuctionsEnding<T>>::take(n) {
+                Self::settle_auction(kitty_id);
+            }
+            for (kitty_id, bidder) in <OffersExpiring<T>>::take(n) {
+                // The offer may have been replaced by a later one since
+                if Self::offer(&(kitty_id, bidder.clone())).map_or(false, |offer| offer.expires_at == n) {
+                    Self::remove_offer(kitty_id, &bidder);
+                }
+            }
+        }
+
         /// Create a new kitty
         pub fn create(origin) {
             let sender = ensure_signed(origin)?;
+            ensure!(!Self::commit_reveal_only(), "Kitties can only be minted by commit and reveal");
             let kitty_id = Self::next_kitty_id()?;
+            Self::ensure_can_receive(&sender, 1)?;
+
+            Self::charge_minting_fee(&sender, T::CreationFee::get(), "Not enough balance to pay creation fee")?;
 
             // Generate a random 128 bit value
             let dna = Self::random_value(&sender);
 
             // Create and store kitty
-            let kitty = Kitty{dna};
+            let kitty = Kitty {
+                birth_block: <system::Module<T>>::block_number(),
+                ..Kitty::legacy(dna)
+            };
             Self::insert_kitty(&sender, kitty_id, kitty);
 
             Self::deposit_event(RawEvent::Created(sender, kitty_id));
         }
 
-        /// Breed kitties
-        pub fn breed(origin, kitty_id_1: T::KittyIndex, kitty_id_2: T::KittyIndex) {
+        /// Breed kitties. Sender must own the dam, and either own the sire or be approved to breed with it
+        pub fn breed(origin, dam_id: T::KittyIndex, sire_id: T::KittyIndex) {
+            let sender = ensure_signed(origin)?;
+            ensure!(!Self::commit_reveal_only(), "Kitties can only be minted by commit and reveal");
+            Self::do_breed(&sender, dam_id, sire_id)?;
+        }
+
+        /// Pay for a new kitty whose dna comes from a secret, revealed with `reveal_mint`.
+        /// `secret_hash` is the hash of the secret.
+        pub fn commit_mint(origin, secret_hash: T::Hash) {
+            let sender = ensure_signed(origin)?;
+            let key = (sender.clone(), secret_hash);
+            ensure!(!<MintCommitments<T>>::exists(&key), "Commitment already exists");
+            Self::ensure_can_receive(&sender, 1)?;
+
+            Self::charge_minting_fee(&sender, T::CreationFee::get(), "Not enough balance to pay creation fee")?;
+
+            let reveal_after = <system::Module<T>>::block_number() + T::RevealDelay::get();
+            <MintCommitments<T>>::insert(&key, MintCommitment { reveal_after, matron: None });
+
+            Self::deposit_event(RawEvent::MintCommitted(sender, secret_hash, reveal_after));
+        }
+
+        /// Breed kitties like `breed`, with the child born from a secret revealed with `reveal_mint`.
+        /// `secret_hash` is the hash of the secret. The dam stays pregnant until the reveal,
+        /// and the child goes to whoever owns her then.
+        pub fn commit_breed(origin, dam_id: T::KittyIndex, sire_id: T::KittyIndex, secret_hash: T::Hash) {
+            let sender = ensure_signed(origin)?;
+            let key = (sender.clone(), secret_hash);
+            ensure!(!<MintCommitments<T>>::exists(&key), "Commitment already exists");
+
+            let (dam, sire) = Self::prepare_breed(&sender, dam_id, sire_id)?;
+            Self::commit_pregnancy(key, dam_id, &dam, sire_id, &sire);
+        }
+
+        /// Breed with a rented sire like `breed_with_rented_sire`, with the child born from a secret
+        /// revealed with `reveal_mint`. `secret_hash` is the hash of the secret.
+        pub fn commit_breed_with_rented_sire(
+            origin,
+            dam_id: T::KittyIndex,
+            sire_id: T::KittyIndex,
+            max_fee: BalanceOf<T>,
+            secret_hash: T::Hash
+        ) {
             let sender = ensure_signed(origin)?;
-            let new_kitty_id = Self::do_breed(&sender, kitty_id_1, kitty_id_2)?;
+            let key = (sender.clone(), secret_hash);
+            ensure!(!<MintCommitments<T>>::exists(&key), "Commitment already exists");
+
+            let (dam, sire) = Self::rent_sire(&sender, dam_id, sire_id, max_fee)?;
+            Self::commit_pregnancy(key, dam_id, &dam, sire_id, &sire);
+        }
+
+        /// Mint the kitty committed to with the hash of `secret`.
+        /// A secret revealed more than `RevealDelay` blocks late forfeits the kitty.
+        pub fn reveal_mint(origin, secret: [u8; 32]) {
+            let sender = ensure_signed(origin)?;
+            let key = (sender.clone(), T::Hashing::hash(&secret[..]));
+
+            let commitment = Self::mint_commitment(&key);
+            ensure!(commitment.is_some(), "No commitment for this secret");
+            let commitment = commitment.unwrap();
+            let now = <system::Module<T>>::block_number();
+            ensure!(commitment.reveal_after < now, "Commitment can not be revealed yet");
+
+            // Old block hashes are pruned, and a committer that could pick the block to reveal in
+            // could try the outcome first, so only a prompt reveal mints
+            if commitment.reveal_after + T::RevealDelay::get() < now {
+                Self::forfeit_commitment(key, commitment.matron);
+                return Ok(());
+            }
+
+            let kitty_id = Self::next_kitty_id()?;
+
+            // Neither the secret nor the block hash is known to anyone else when committing
+            let random = (secret, <system::Module<T>>::block_hash(commitment.reveal_after), &sender)
+                .using_encoded(blake2_128);
+
+            match commitment.matron {
+                Some(matron_id) => {
+                    // The pregnant matron can not be burned, the child goes to her current owner
+                    let pregnancy = Self::pregnancy(matron_id);
+                    let matron = Self::kitty(matron_id);
+                    let owner = Self::kitty_owner(matron_id);
+                    ensure!(pregnancy.is_some() && matron.is_some() && owner.is_some(), "Invalid matron");
+                    let (pregnancy, matron, owner) = (pregnancy.unwrap(), matron.unwrap(), owner.unwrap());
+                    Self::ensure_can_receive(&owner, 1)?;
+
+                    <MintCommitments<T>>::remove(&key);
+                    <CommittedPregnancies<T>>::remove(matron_id);
+                    <Pregnancies<T>>::remove(matron_id);
+                    Self::insert_child(&owner, kitty_id, matron_id, &matron, &pregnancy, random);
+
+                    Self::deposit_event(RawEvent::Born(owner, kitty_id, matron_id));
+                }
+                None => {
+                    Self::ensure_can_receive(&sender, 1)?;
+
+                    <MintCommitments<T>>::remove(&key);
+                    let kitty = Kitty {
+                        birth_block: <system::Module<T>>::block_number(),
+                        ..Kitty::legacy(random)
+                    };
+                    Self::insert_kitty(&sender, kitty_id, kitty);
+
+                    Self::deposit_event(RawEvent::Created(sender, kitty_id));
+                }
+            }
+        }
 
-            Self::deposit_event(RawEvent::Created(sender, new_kitty_id));
+        /// Deliver a child whose due block has passed, or end a committed pregnancy
+        /// whose secret was not revealed in time. Anyone can call this
+        pub fn give_birth(origin, matron_id: T::KittyIndex) {
+            ensure_signed(origin)?;
+            Self::do_give_birth(matron_id)?;
         }
 
         /// Transfer kitty
@@ -108,10 +574,64 @@ decl_module! {
             Self::deposit_event(RawEvent::Transferred(sender, recipient, kitty_id));
         }
 
+        /// Approve an account to transfer a kitty, or clear the approval with `None`.
+        /// Can be called by the owner or one of its operators.
+        pub fn approve(origin, kitty_id: T::KittyIndex, spender: Option<T::AccountId>) {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::kitty_owner(kitty_id);
+            ensure!(owner.is_some(), "Kitty does not exist");
+            let owner = owner.unwrap();
+            ensure!(
+                owner == sender || Self::operator_approval(&(owner.clone(), sender)),
+                "Only owner or operator can approve"
+            );
+
+            match spender {
+                Some(ref spender) => <KittyApprovals<T>>::insert(kitty_id, spender),
+                None => <KittyApprovals<T>>::remove(kitty_id),
+            }
+
+            Self::deposit_event(RawEvent::Approval(owner, spender, kitty_id));
+        }
+
+        /// Allow or disallow an operator to manage all kitties of the sender
+        pub fn set_approval_for_all(origin, operator: T::AccountId, approved: bool) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(sender != operator, "Can not approve self as operator");
+
+            if approved {
+                <OperatorApprovals<T>>::insert(&(sender.clone(), operator.clone()), true);
+            } else {
+                <OperatorApprovals<T>>::remove(&(sender.clone(), operator.clone()));
+            }
+
+            Self::deposit_event(RawEvent::ApprovalForAll(sender, operator, approved));
+        }
+
+        /// Transfer a kitty on behalf of its owner. Sender must be the owner, approved for the kitty, or an operator
+        pub fn transfer_from(origin, from: T::AccountId, to: T::AccountId, kitty_id: T::KittyIndex) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(
+                sender == from
+                    || Self::kitty_approval(kitty_id).as_ref() == Some(&sender)
+                    || Self::operator_approval(&(from.clone(), sender)),
+                "Not allowed to transfer kitty"
+            );
+            Self::do_transfer(&from, &to, kitty_id)?;
+
+            Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+        }
+
         pub fn ask(origin, kitty_id: T::KittyIndex, price: Option<BalanceOf<T>>) {
             let sender = ensure_signed(origin)?;
 
             ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))), "Only owner can set price for kitty");
+            Self::ensure_unlocked(kitty_id)?;
+            // The migration would drop the new price as stale
+            ensure!(Self::is_migrated(2, kitty_id), "Kitty prices are being migrated");
 
             if let Some(price) = price {
                 <KittyPrices<T>>::insert(kitty_id, price);
@@ -129,161 +649,2258 @@ decl_module! {
             ensure!(owner.is_some(), "Kitty does not exist");
             let owner = owner.unwrap();
 
-            let kitty_price = Self::kitty_price(kitty_id);
+            let kitty_price = Self::price_of(kitty_id);
             ensure!(kitty_price.is_some(), "Kitty not for sale");
             let kitty_price = kitty_price.unwrap();
 
             ensure!(price >= kitty_price, "Price is too low");
+            Self::ensure_can_receive(&sender, 1)?;
+
+            let (fee, royalty) = Self::pay_sale(&sender, &owner, &[kitty_id], kitty_price, false)?;
+
+            <DutchAuctions<T>>::remove(kitty_id);
+            Self::change_owner(&owner, &sender, kitty_id);
+
+            Self::deposit_event(RawEvent::Sold(owner, sender, kitty_id, kitty_price, fee, royalty));
+        }
+
+        /// Sell a kitty with a price decaying linearly from `start_price` to `end_price` over `duration` blocks.
+        /// The kitty is locked against transfer until sold or cancelled.
+        pub fn create_dutch_auction(
+            origin,
+            kitty_id: T::KittyIndex,
+            start_price: BalanceOf<T>,
+            end_price: BalanceOf<T>,
+            duration: T::BlockNumber
+        ) {
+            let sender = ensure_signed(origin)?;
 
-            T::Currency::transfer(&sender, &owner, kitty_price)?;
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))), "Only owner can auction kitty");
+            Self::ensure_unlocked(kitty_id)?;
+            ensure!(start_price >= end_price, "Start price must not be below end price");
+            ensure!(!duration.is_zero(), "Auction duration must not be zero");
 
+            // The auction price replaces any fixed price
             <KittyPrices<T>>::remove(kitty_id);
+            <DutchAuctions<T>>::insert(kitty_id, DutchAuction {
+                seller: sender.clone(),
+                start_price,
+                end_price,
+                start_block: <system::Module<T>>::block_number(),
+                duration,
+            });
 
-            <OwnedKittiesList<T>>::remove(&owner, kitty_id);
-            <OwnedKittiesList<T>>::append(&sender, kitty_id);
-            <KittyOwners<T>>::insert(kitty_id, &sender);
+            Self::deposit_event(RawEvent::DutchAuctionCreated(sender, kitty_id, start_price, end_price, duration));
+        }
+
+        /// Cancel a dutch auction
+        pub fn cancel_dutch_auction(origin, kitty_id: T::KittyIndex) {
+            let sender = ensure_signed(origin)?;
+
+            let auction = Self::dutch_auction(kitty_id);
+            ensure!(auction.is_some(), "Kitty is not in dutch auction");
+            ensure!(auction.unwrap().seller == sender, "Only seller can cancel auction");
 
-            Self::deposit_event(RawEvent::Sold(owner, sender, kitty_id, kitty_price));
+            <DutchAuctions<T>>::remove(kitty_id);
+
+            Self::deposit_event(RawEvent::DutchAuctionCancelled(sender, kitty_id));
         }
-    }
-}
 
-fn combine_dna(dna1: u8, dna2: u8, selector: u8) -> u8 {
-    ((selector & dna1) | (!selector & dna2))
-}
+        /// Auction a kitty to the highest bidder, settled at `end_block`.
+        /// The kitty is locked against transfer until then.
+        pub fn start_auction(origin, kitty_id: T::KittyIndex, reserve: BalanceOf<T>, end_block: T::BlockNumber) {
+            let sender = ensure_signed(origin)?;
 
-impl<T: Trait> Module<T> {
-    fn random_value(sender: &T::AccountId) -> [u8; 16] {
-        let payload = (
-            <system::Module<T>>::random_seed(),
-            sender.clone(),
-            <system::Module<T>>::extrinsic_index(),
-            <system::Module<T>>::block_number(),
-        );
-        payload.using_encoded(blake2_128)
-    }
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))), "Only owner can auction kitty");
+            Self::ensure_unlocked(kitty_id)?;
+            ensure!(end_block > <system::Module<T>>::block_number(), "Auction must end in the future");
+            ensure!(Self::has_room(Self::auctions_ending(end_block).len()), "Too many auctions end in this block");
 
-    fn next_kitty_id() -> result::Result<T::KittyIndex, &'static str> {
-        let kitty_id = Self::kitties_count();
-        if kitty_id == T::KittyIndex::max_value() {
-            return Err("Kitties count overflow");
+            <KittyPrices<T>>::remove(kitty_id);
+            <Auctions<T>>::insert(kitty_id, Auction {
+                seller: sender.clone(),
+                reserve,
+                end_block,
+                highest_bid: None,
+            });
+            <AuctionsEnding<T>>::mutate(end_block, |kitties| kitties.push(kitty_id));
+
+            Self::deposit_event(RawEvent::AuctionStarted(sender, kitty_id, reserve, end_block));
         }
-        Ok(kitty_id)
-    }
 
-    fn insert_owned_kitty(owner: &T::AccountId, kitty_id: T::KittyIndex) {
-        <OwnedKittiesList<T>>::append(owner, kitty_id);
-    }
+        /// Bid in an auction. The bid is reserved, and the previous highest bid is released
+        pub fn bid(origin, kitty_id: T::KittyIndex, amount: BalanceOf<T>) {
+            let sender = ensure_signed(origin)?;
 
-    fn insert_kitty(owner: &T::AccountId, kitty_id: T::KittyIndex, kitty: Kitty) {
-        // Create and store kitty
-        <Kitties<T>>::insert(kitty_id, kitty);
-        <KittiesCount<T>>::put(kitty_id + One::one());
-        <KittyOwners<T>>::insert(kitty_id, owner);
+            let auction = Self::auction(kitty_id);
+            ensure!(auction.is_some(), "Kitty is not in auction");
+            let mut auction = auction.unwrap();
 
-        Self::insert_owned_kitty(owner, kitty_id);
-    }
+            ensure!(<system::Module<T>>::block_number() < auction.end_block, "Auction has ended");
+            ensure!(auction.seller != sender, "Seller can not bid");
+            ensure!(amount >= auction.reserve, "Bid is below reserve");
+            Self::ensure_can_receive(&sender, 1)?;
 
-    fn do_breed(
-        sender: &T::AccountId,
-        kitty_id_1: T::KittyIndex,
-        kitty_id_2: T::KittyIndex,
-    ) -> result::Result<T::KittyIndex, &'static str> {
-        let kitty1 = Self::kitty(kitty_id_1);
-        let kitty2 = Self::kitty(kitty_id_2);
+            match auction.highest_bid {
+                Some((ref bidder, highest)) => {
+                    ensure!(amount > highest, "Bid is too low");
+                    if *bidder == sender {
+                        T::Currency::reserve(&sender, amount - highest)?;
+                    } else {
+                        T::Currency::reserve(&sender, amount)?;
+                        T::Currency::unreserve(bidder, highest);
+                    }
+                },
+                None => T::Currency::reserve(&sender, amount)?,
+            }
 
-        ensure!(kitty1.is_some(), "Invalid kitty_id_1");
-        ensure!(kitty2.is_some(), "Invalid kitty_id_2");
-        ensure!(kitty_id_1 != kitty_id_2, "Needs different parents");
+            auction.highest_bid = Some((sender.clone(), amount));
+            <Auctions<T>>::insert(kitty_id, auction);
 
-        let new_kitty_id = Self::next_kitty_id()?;
+            Self::deposit_event(RawEvent::Bid(sender, kitty_id, amount));
+        }
+
+        /// Offer to buy a kitty, whether or not it is for sale. The amount is reserved until `expires_at`
+        pub fn make_offer(origin, kitty_id: T::KittyIndex, amount: BalanceOf<T>, expires_at: T::BlockNumber) {
+            let sender = ensure_signed(origin)?;
+
+            let owner = Self::kitty_owner(kitty_id);
+            ensure!(owner.is_some(), "Kitty does not exist");
+            ensure!(owner.unwrap() != sender, "Can not make offer for own kitty");
+            ensure!(expires_at > <system::Module<T>>::block_number(), "Offer must expire in the future");
+            ensure!(!<Offers<T>>::exists(&(kitty_id, sender.clone())), "Offer already made");
+            ensure!(amount >= T::MinimumOffer::get(), "Offer is below minimum");
+            ensure!(
+                (Self::kitty_offerers(kitty_id).len() as u32) < T::MaxOffersPerKitty::get(),
+                "Kitty has too many offers"
+            );
+            ensure!(Self::has_room(Self::offers_expiring(expires_at).len()), "Too many offers expire in this block");
+
+            T::Currency::reserve(&sender, amount)?;
 
-        let kitty1_dna = kitty1.unwrap().dna;
-        let kitty2_dna = kitty2.unwrap().dna;
-        let selector = Self::random_value(&sender);
+            <Offers<T>>::insert(&(kitty_id, sender.clone()), Offer { amount, expires_at });
+            <KittyOfferers<T>>::mutate(kitty_id, |offerers| offerers.push(sender.clone()));
+            <OffersExpiring<T>>::mutate(expires_at, |offers| offers.push((kitty_id, sender.clone())));
 
-        let mut new_dna = [0u8; 16];
-        for i in 0..kitty1_dna.len() {
-            new_dna[i] = combine_dna(kitty1_dna[i], kitty2_dna[i], selector[i]);
+            Self::deposit_event(RawEvent::OfferMade(sender, kitty_id, amount, expires_at));
         }
 
-        let new_kitty = Kitty{dna:new_dna};
-        Self::insert_kitty(sender, new_kitty_id, new_kitty);
-        Ok(new_kitty_id)
-    }
+        /// Sell a kitty to a bidder at the offered amount
+        pub fn accept_offer(origin, kitty_id: T::KittyIndex, bidder: T::AccountId) {
+            let sender = ensure_signed(origin)?;
 
-    fn do_transfer(
-        sender: &T::AccountId,
-        recipient: &T::AccountId,
-        kitty_id: T::KittyIndex,
-    ) -> Result {
-        // Check if the kitty exsit
-        let transfer_kitty = Self::kitty(kitty_id);
-        ensure!(transfer_kitty.is_some(), "Invalid transfer kitty");
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))), "Only owner can accept offer");
+            Self::ensure_unlocked(kitty_id)?;
 
-        // Check if the sender own this kitty
-        ensure!(
-            <OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))),
-            "Only owner can transfer kitty"
-        );
+            let offer = Self::offer(&(kitty_id, bidder.clone()));
+            ensure!(offer.is_some(), "Offer does not exist");
+            let offer = offer.unwrap();
+            ensure!(offer.expires_at > <system::Module<T>>::block_number(), "Offer has expired");
+            Self::ensure_can_receive(&bidder, 1)?;
 
-        <OwnedKittiesList<T>>::remove(&sender, kitty_id);
-        <OwnedKittiesList<T>>::append(&recipient, kitty_id);
-        <KittyOwners<T>>::insert(kitty_id, recipient);
-        Ok(())
-    }
-}
+            let (fee, royalty) = Self::pay_sale(&bidder, &sender, &[kitty_id], offer.amount, true)?;
+            <Offers<T>>::remove(&(kitty_id, bidder.clone()));
+            <KittyOfferers<T>>::mutate(kitty_id, |offerers| offerers.retain(|offerer| *offerer != bidder));
 
-/// tests for this module
-#[cfg(test)]
-mod tests {
-    use super::*;
+            Self::change_owner(&sender, &bidder, kitty_id);
 
-    use primitives::{Blake2Hasher, H256};
-    use runtime_io::with_externalities;
-    use runtime_primitives::{
-        testing::{Digest, DigestItem, Header},
-        traits::{BlakeTwo256, IdentityLookup},
-        BuildStorage,
-    };
-    use support::{assert_ok, impl_outer_origin};
+            Self::deposit_event(RawEvent::Sold(sender, bidder, kitty_id, offer.amount, fee, royalty));
+        }
 
-    impl_outer_origin! {
-        pub enum Origin for Test {}
-    }
+        /// Withdraw an offer and release the reserved amount
+        pub fn withdraw_offer(origin, kitty_id: T::KittyIndex) {
+            let sender = ensure_signed(origin)?;
 
-    // For testing the module, we construct most of a mock runtime. This means
-    // first constructing a configuration type (`Test`) which `impl`s each of the
-    // configuration traits of modules we want to use.
-    #[derive(Clone, Eq, PartialEq, Debug)]
-    pub struct Test;
-    impl system::Trait for Test {
-        type Origin = Origin;
-        type Index = u64;
-        type BlockNumber = u64;
-        type Hash = H256;
-        type Hashing = BlakeTwo256;
-        type Digest = Digest;
-        type AccountId = u64;
-        type Lookup = IdentityLookup<Self::AccountId>;
-        type Header = Header;
-        type Event = ();
-        type Log = DigestItem;
-    }
-    impl Trait for Test {
-        type KittyIndex = u32;
-    }
-    type KittyModule = Module<Test>;
-    type OwnedKittiesTest = OwnedKitties<Test>;
+            ensure!(<Offers<T>>::exists(&(kitty_id, sender.clone())), "Offer does not exist");
 
-    // This function basically just builds a genesis storage key/value store according to
-    // our desired mockup.
-    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
-        system::GenesisConfig::<Test>::default()
-            .build_storage()
-            .unwrap()
-            .0
-            .into()
+            Self::remove_offer(kitty_id, &sender);
+        }
+
+        /// Propose to exchange one of the sender's kitties for another one, optionally paying `top_up` on top
+        pub fn propose_swap(
+            origin,
+            my_kitty: T::KittyIndex,
+            their_kitty: T::KittyIndex,
+            top_up: Option<BalanceOf<T>>,
+            expires_at: T::BlockNumber
+        ) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(my_kitty))), "Only owner can swap kitty");
+            let their_owner = Self::kitty_owner(their_kitty);
+            ensure!(their_owner.is_some(), "Kitty does not exist");
+            ensure!(their_owner.unwrap() != sender, "Can not swap with own kitty");
+            ensure!(expires_at > <system::Module<T>>::block_number(), "Swap must expire in the future");
+            let max_swaps = T::MaxOffersPerKitty::get() as usize;
+            ensure!(
+                Self::kitty_swaps(my_kitty).len() < max_swaps && Self::kitty_swaps(their_kitty).len() < max_swaps,
+                "Kitty is in too many swaps"
+            );
+
+            let swap_id = Self::next_swap_id();
+            <NextSwapId<T>>::put(swap_id + 1);
+            <KittySwaps<T>>::mutate(my_kitty, |swaps| swaps.push(swap_id));
+            <KittySwaps<T>>::mutate(their_kitty, |swaps| swaps.push(swap_id));
+            <Swaps<T>>::insert(swap_id, Swap {
+                proposer: sender.clone(),
+                offered: my_kitty,
+                wanted: their_kitty,
+                top_up,
+                expires_at,
+            });
+
+            Self::deposit_event(RawEvent::SwapProposed(sender, swap_id, my_kitty, their_kitty));
+        }
+
+        /// Accept a swap for a kitty the sender owns. Both kitties and the top up change hands at once
+        pub fn accept_swap(origin, swap_id: SwapId) {
+            let sender = ensure_signed(origin)?;
+
+            let swap = Self::swap(swap_id);
+            ensure!(swap.is_some(), "Swap does not exist");
+            let swap = swap.unwrap();
+
+            ensure!(swap.expires_at > <system::Module<T>>::block_number(), "Swap has expired");
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(swap.wanted))), "Only owner can accept swap");
+            ensure!(
+                <OwnedKitties<T>>::exists(&(swap.proposer.clone(), Some(swap.offered))),
+                "Proposer no longer owns kitty"
+            );
+            Self::ensure_unlocked(swap.offered)?;
+            Self::ensure_unlocked(swap.wanted)?;
+
+            if let Some(top_up) = swap.top_up {
+                T::Currency::transfer(&swap.proposer, &sender, top_up)?;
+            }
+
+            Self::remove_swap(swap_id, &swap);
+            Self::change_owner(&swap.proposer, &sender, swap.offered);
+            Self::change_owner(&sender, &swap.proposer, swap.wanted);
+
+            Self::deposit_event(RawEvent::Swapped(swap.proposer, sender, swap_id));
+        }
+
+        /// Cancel a swap. The proposer can cancel at any time, anyone can clear an expired swap
+        pub fn cancel_swap(origin, swap_id: SwapId) {
+            let sender = ensure_signed(origin)?;
+
+            let swap = Self::swap(swap_id);
+            ensure!(swap.is_some(), "Swap does not exist");
+            let swap = swap.unwrap();
+            ensure!(
+                swap.proposer == sender || swap.expires_at <= <system::Module<T>>::block_number(),
+                "Only proposer can cancel swap"
+            );
+
+            Self::remove_swap(swap_id, &swap);
+
+            Self::deposit_event(RawEvent::SwapCancelled(swap_id));
+        }
+
+        /// Sell several kitties as one lot. The kitties are locked until sold or cancelled
+        pub fn ask_bundle(origin, kitties: Vec<T::KittyIndex>, price: BalanceOf<T>) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(!kitties.is_empty(), "Bundle is empty");
+            ensure!(kitties.len() <= MAX_BUNDLE_SIZE, "Bundle is too large");
+            for (i, kitty_id) in kitties.iter().enumerate() {
+                ensure!(!kitties[..i].contains(kitty_id), "Kitty is repeated in bundle");
+                ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(*kitty_id))), "Only owner can sell kitty");
+                Self::ensure_unlocked(*kitty_id)?;
+            }
+
+            let bundle_id = Self::next_bundle_id();
+            <NextBundleId<T>>::put(bundle_id + 1);
+            for kitty_id in &kitties {
+                <KittyPrices<T>>::remove(kitty_id);
+                <KittyBundles<T>>::insert(kitty_id, bundle_id);
+            }
+            <Bundles<T>>::insert(bundle_id, Bundle {
+                seller: sender.clone(),
+                kitties,
+                price,
+            });
+
+            Self::deposit_event(RawEvent::BundleAsk(sender, bundle_id, price));
+        }
+
+        /// Buy all kitties of a bundle
+        pub fn buy_bundle(origin, bundle_id: BundleId, max_price: BalanceOf<T>) {
+            let sender = ensure_signed(origin)?;
+
+            let bundle = Self::bundle(bundle_id);
+            ensure!(bundle.is_some(), "Bundle does not exist");
+            let bundle = bundle.unwrap();
+
+            ensure!(bundle.price <= max_price, "Price is too low");
+            Self::ensure_can_receive(&sender, bundle.kitties.len() as u32)?;
+
+            let (fee, royalty) = Self::pay_sale(&sender, &bundle.seller, &bundle.kitties, bundle.price, false)?;
+
+            <Bundles<T>>::remove(bundle_id);
+            for kitty_id in &bundle.kitties {
+                <KittyBundles<T>>::remove(kitty_id);
+                Self::change_owner(&bundle.seller, &sender, *kitty_id);
+            }
+
+            Self::deposit_event(RawEvent::BundleSold(bundle.seller, sender, bundle_id, bundle.price, fee, royalty));
+        }
+
+        /// Cancel a bundle and unlock its kitties
+        pub fn cancel_bundle(origin, bundle_id: BundleId) {
+            let sender = ensure_signed(origin)?;
+
+            let bundle = Self::bundle(bundle_id);
+            ensure!(bundle.is_some(), "Bundle does not exist");
+            let bundle = bundle.unwrap();
+            ensure!(bundle.seller == sender, "Only seller can cancel bundle");
+
+            <Bundles<T>>::remove(bundle_id);
+            for kitty_id in &bundle.kitties {
+                <KittyBundles<T>>::remove(kitty_id);
+            }
+
+            Self::deposit_event(RawEvent::BundleCancelled(bundle_id));
+        }
+
+        /// Allow another account to breed with a sire
+        pub fn approve_siring(origin, sire_id: T::KittyIndex, account: T::AccountId) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(sire_id))), "Only owner can approve siring");
+
+            <SiringApprovals<T>>::insert(sire_id, &account);
+
+            Self::deposit_event(RawEvent::SiringApproved(sender, sire_id, account));
+        }
+
+        /// List a kitty for rent as a sire, or remove the listing
+        pub fn list_sire(origin, sire_id: T::KittyIndex, fee: Option<BalanceOf<T>>) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(sire_id))), "Only owner can list sire");
+
+            if let Some(fee) = fee {
+                <SiringPrices<T>>::insert(sire_id, fee);
+            } else {
+                <SiringPrices<T>>::remove(sire_id);
+            }
+
+            Self::deposit_event(RawEvent::SireListed(sender, sire_id, fee));
+        }
+
+        /// Pay the siring fee to the sire owner and breed with the rented sire
+        pub fn breed_with_rented_sire(origin, dam_id: T::KittyIndex, sire_id: T::KittyIndex, max_fee: BalanceOf<T>) {
+            let sender = ensure_signed(origin)?;
+            ensure!(!Self::commit_reveal_only(), "Kitties can only be minted by commit and reveal");
+
+            let (dam, sire) = Self::rent_sire(&sender, dam_id, sire_id, max_fee)?;
+            Self::conceive(&sender, dam_id, &dam, sire_id, &sire)?;
+        }
+
+        /// Set the name of a kitty
+        pub fn set_name(origin, kitty_id: T::KittyIndex, name: Vec<u8>) {
+            let sender = ensure_signed(origin)?;
+
+            ensure!(<OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))), "Only owner can name kitty");
+            ensure!(name.len() <= MAX_NAME_LENGTH, "Kitty name is too long");
+
+            <Kitties<T>>::mutate(kitty_id, |kitty| {
+                if let Some(kitty) = kitty {
+                    kitty.name = name;
+                }
+            });
+
+            Self::deposit_event(RawEvent::Renamed(sender, kitty_id));
+        }
+
+        /// Burn a kitty. The kitty id is never reused.
+        pub fn burn(origin, kitty_id: T::KittyIndex) {
+            let sender = ensure_signed(origin)?;
+            Self::do_burn(&sender, kitty_id)?;
+
+            Self::deposit_event(RawEvent::Burned(sender, kitty_id));
+        }
+
+        /// Override how many times a kitty can breed. `None` goes back to `MaxBreedsPerKitty`.
+        pub fn set_max_breeds(origin, max_breeds: Option<u32>) {
+            ensure_root(origin)?;
+
+            match max_breeds {
+                Some(max_breeds) => <MaxBreedsOverride<T>>::put(max_breeds),
+                None => <MaxBreedsOverride<T>>::kill(),
+            }
+        }
+
+        /// Switch off `create`, `breed` and `breed_with_rented_sire`, whose randomness block authors can bias
+        pub fn set_commit_reveal_only(origin, enabled: bool) {
+            ensure_root(origin)?;
+            <CommitRevealOnly<T>>::put(enabled);
+        }
+    }
+}
+
+/// Cap on the cooldown doubling, i.e. the longest cooldown is `BreedingCooldown * 2^13`
+const MAX_COOLDOWN_INDEX: u32 = 13;
+
+/// Bump when the stored format changes, and upgrade old entries in `Module::migrate`
+const CURRENT_STORAGE_VERSION: u32 = 4;
+
+impl<T: Trait> Module<T> {
+    fn random_value<S: Encode>(subject: &S) -> [u8; 16] {
+        let payload = (
+            subject.using_encoded(T::Randomness::random),
+            <system::Module<T>>::extrinsic_index(),
+            <system::Module<T>>::block_number(),
+        );
+        payload.using_encoded(blake2_128)
+    }
+
+    /// Up to `limit` kitties of an account, following `start` or from the first one
+    pub fn kitties_of(account: &T::AccountId, start: Option<T::KittyIndex>, limit: u32) -> Vec<T::KittyIndex> {
+        let mut kitties = Vec::new();
+        let mut cursor = start;
+        while (kitties.len() as u32) < limit {
+            match <OwnedKittiesList<T>>::next(account, cursor) {
+                Some(kitty_id) => {
+                    kitties.push(kitty_id);
+                    cursor = Some(kitty_id);
+                }
+                None => break,
+            }
+        }
+        kitties
+    }
+
+    /// Price a kitty can be bought for right now, from a dutch auction or an ask
+    pub fn price_of(kitty_id: T::KittyIndex) -> Option<BalanceOf<T>> {
+        match Self::dutch_auction(kitty_id) {
+            Some(auction) => Some(Self::dutch_auction_price(&auction)),
+            None => Self::kitty_price(kitty_id),
+        }
+    }
+
+    /// Up to `limit` kitties for sale with their prices, from kitty id `start` on
+    pub fn kitties_for_sale(start: T::KittyIndex, limit: u32) -> Vec<(T::KittyIndex, BalanceOf<T>)> {
+        let mut kitties = Vec::new();
+        let mut kitty_id = start;
+        while kitty_id < Self::kitties_count() && (kitties.len() as u32) < limit {
+            if let Some(price) = Self::price_of(kitty_id) {
+                kitties.push((kitty_id, price));
+            }
+            kitty_id = kitty_id + One::one();
+        }
+        kitties
+    }
+
+    /// Looks of a kitty decoded from its dna
+    pub fn kitty_traits(kitty_id: T::KittyIndex) -> Option<KittyTraits> {
+        Self::kitty(kitty_id).map(|kitty| KittyTraits::from_dna(&kitty.dna))
+    }
+
+    /// Current price of a dutch auction
+    pub fn dutch_auction_price(auction: &DutchAuctionOf<T>) -> BalanceOf<T> {
+        let elapsed = <system::Module<T>>::block_number() - auction.start_block;
+        if elapsed >= auction.duration {
+            return auction.end_price;
+        }
+
+        let decay = (auction.start_price - auction.end_price)
+            * BalanceOf::<T>::sa(elapsed.as_())
+            / BalanceOf::<T>::sa(auction.duration.as_());
+        auction.start_price - decay
+    }
+
+    /// Kitties listed in an auction can not change hands outside of it
+    fn ensure_unlocked(kitty_id: T::KittyIndex) -> Result {
+        ensure!(!<DutchAuctions<T>>::exists(kitty_id), "Kitty is in dutch auction");
+        ensure!(!<Auctions<T>>::exists(kitty_id), "Kitty is in auction");
+        ensure!(!<KittyBundles<T>>::exists(kitty_id), "Kitty is in bundle");
+        Ok(())
+    }
+
+    /// Whether a block with `scheduled` entries in one of its `on_finalize` lists can take another
+    fn has_room(scheduled: usize) -> bool {
+        (scheduled as u32) < T::MaxScheduledPerBlock::get()
+    }
+
+    /// Remove an offer and release the reserved amount
+    fn remove_offer(kitty_id: T::KittyIndex, bidder: &T::AccountId) {
+        if let Some(offer) = <Offers<T>>::take(&(kitty_id, bidder.clone())) {
+            <KittyOfferers<T>>::mutate(kitty_id, |offerers| offerers.retain(|offerer| offerer != bidder));
+            T::Currency::unreserve(bidder, offer.amount);
+
+            Self::deposit_event(RawEvent::OfferRemoved(bidder.clone(), kitty_id));
+        }
+    }
+
+    /// Remove a swap from both kitties it references
+    fn remove_swap(swap_id: SwapId, swap: &SwapOf<T>) {
+        <Swaps<T>>::remove(swap_id);
+        <KittySwaps<T>>::mutate(swap.offered, |swaps| swaps.retain(|id| *id != swap_id));
+        <KittySwaps<T>>::mutate(swap.wanted, |swaps| swaps.retain(|id| *id != swap_id));
+    }
+
+    /// Give the kitty to the highest bidder and pay the seller from the reserved bid
+    fn settle_auction(kitty_id: T::KittyIndex) {
+        let auction = match <Auctions<T>>::take(kitty_id) {
+            Some(auction) => auction,
+            None => return,
+        };
+
+        match auction.highest_bid {
+            Some((bidder, amount)) => match Self::ensure_can_receive(&bidder, 1)
+                .and_then(|_| Self::pay_sale(&bidder, &auction.seller, &[kitty_id], amount, true))
+            {
+                Ok((fee, royalty)) => {
+                    Self::change_owner(&auction.seller, &bidder, kitty_id);
+
+                    Self::deposit_event(RawEvent::Sold(auction.seller, bidder, kitty_id, amount, fee, royalty));
+                },
+                // `bid` checks the cap, but the bidder may have got more kitties since.
+                // The kitty stays and only this bid is released, as `pay_sale` moves nothing when it fails
+                Err(_) => {
+                    T::Currency::unreserve(&bidder, amount);
+                    Self::deposit_event(RawEvent::AuctionFailed(auction.seller, bidder, kitty_id, amount));
+                },
+            },
+            None => Self::deposit_event(RawEvent::AuctionEnded(auction.seller, kitty_id)),
+        }
+    }
+
+    /// Pay the seller, the market fee and the breeder royalties for a sale, from the free balance of the buyer,
+    /// or from its reserved balance for bids and offers. Returns (market fee, total royalty).
+    /// The royalty of each kitty is taken from an equal share of the price. Royalties that can not be paid,
+    /// e.g. to a reaped breeder account, go to the seller.
+    /// Every payment is checked before any funds move, as a failed call does not undo storage changes.
+    /// The price is then taken from the reserve in one go and split, so nothing can fail halfway.
+    fn pay_sale(
+        buyer: &T::AccountId,
+        seller: &T::AccountId,
+        kitties: &[T::KittyIndex],
+        price: BalanceOf<T>,
+        from_reserved: bool,
+    ) -> result::Result<(BalanceOf<T>, BalanceOf<T>), &'static str> {
+        let fee = T::MarketFee::get() * price;
+        let share = price / BalanceOf::<T>::sa(cmp::max(kitties.len(), 1) as u64);
+        let royalties: Vec<(T::AccountId, BalanceOf<T>)> = kitties.iter()
+            .filter_map(|kitty_id| Self::kitty_breeder(kitty_id))
+            .filter(|breeder| breeder != seller)
+            .map(|breeder| (breeder, T::BreederRoyalty::get() * share))
+            .filter(|(breeder, amount)| Self::can_be_paid(breeder, *amount))
+            .collect();
+        let royalty = royalties.iter().fold(Zero::zero(), |total: BalanceOf<T>, (_, amount)| total + *amount);
+        ensure!(fee + royalty <= price, "Fees exceed price");
+        let proceeds = price - fee - royalty;
+        ensure!(proceeds.is_zero() || Self::can_be_paid(seller, proceeds), "Seller can not be paid");
+
+        if from_reserved {
+            ensure!(T::Currency::reserved_balance(buyer) >= price, "Not enough reserved balance");
+        } else {
+            ensure!(T::Currency::free_balance(buyer) >= price, "Not enough balance");
+            // The only step that can fail, and it moves nothing when it does
+            T::Currency::reserve(buyer, price)?;
+        }
+
+        let (payment, _) = T::Currency::slash_reserved(buyer, price);
+        let (fee_payment, mut payment) = payment.split(fee);
+        T::MarketFeeDestination::on_unbalanced(fee_payment);
+        for (breeder, amount) in royalties {
+            let (royalty_payment, rest) = payment.split(amount);
+            T::Currency::resolve_creating(&breeder, royalty_payment);
+            payment = rest;
+        }
+        if !proceeds.is_zero() {
+            T::Currency::resolve_creating(seller, payment);
+        }
+
+        Ok((fee, royalty))
+    }
+
+    /// Whether a transfer of `amount` to `who` can succeed, i.e. it is not zero and either goes to
+    /// an existing account or is enough to create one
+    fn can_be_paid(who: &T::AccountId, amount: BalanceOf<T>) -> bool {
+        !amount.is_zero() && (!T::Currency::free_balance(who).is_zero() || amount >= T::Currency::minimum_balance())
+    }
+
+    /// Withdraw a creation or breeding fee and hand it to `MintingFeeDestination`
+    fn charge_minting_fee(who: &T::AccountId, fee: BalanceOf<T>, insufficient: &'static str) -> Result {
+        if fee.is_zero() {
+            return Ok(());
+        }
+        ensure!(T::Currency::free_balance(who) >= fee, insufficient);
+
+        let imbalance = T::Currency::withdraw(who, fee, WithdrawReason::Fee, ExistenceRequirement::KeepAlive)?;
+        T::MintingFeeDestination::on_unbalanced(imbalance);
+        Ok(())
+    }
+
+    fn next_kitty_id() -> result::Result<T::KittyIndex, &'static str> {
+        let kitty_id = Self::kitties_count();
+        if kitty_id == T::KittyIndex::max_value() {
+            return Err("Kitties count overflow");
+        }
+        Ok(kitty_id)
+    }
+
+    /// Check an account can get `count` more kitties under `MaxKittiesPerAccount`
+    fn ensure_can_receive(who: &T::AccountId, count: u32) -> Result {
+        if let Some(max) = T::MaxKittiesPerAccount::get() {
+            ensure!(Self::owned_kitties_count(who).saturating_add(count) <= max, "Account owns too many kitties");
+        }
+        Ok(())
+    }
+
+    fn insert_owned_kitty(owner: &T::AccountId, kitty_id: T::KittyIndex) {
+        <OwnedKittiesList<T>>::append(owner, kitty_id);
+        // Kitties the count migration did not get to yet are counted for their owner by then
+        if Self::is_migrated(3, kitty_id) {
+            <OwnedKittiesCount<T>>::mutate(owner, |count| *count += 1);
+        }
+    }
+
+    fn remove_owned_kitty(owner: &T::AccountId, kitty_id: T::KittyIndex) {
+        <OwnedKittiesList<T>>::remove(owner, kitty_id);
+        if Self::is_migrated(3, kitty_id) {
+            <OwnedKittiesCount<T>>::mutate(owner, |count| *count = count.saturating_sub(1));
+        }
+    }
+
+    fn insert_kitty(owner: &T::AccountId, kitty_id: T::KittyIndex, kitty: KittyOf<T>) {
+        // Create and store kitty
+        <Kitties<T>>::insert(kitty_id, kitty);
+        <KittiesCount<T>>::put(kitty_id + One::one());
+        <KittyOwners<T>>::insert(kitty_id, owner);
+        <KittyBreeders<T>>::insert(kitty_id, owner);
+
+        Self::insert_owned_kitty(owner, kitty_id);
+    }
+
+    /// Checks everything needed to breed except the permission to use the sire
+    fn check_breed(
+        sender: &T::AccountId,
+        dam_id: T::KittyIndex,
+        sire_id: T::KittyIndex,
+    ) -> result::Result<(KittyOf<T>, KittyOf<T>), &'static str> {
+        let dam = Self::kitty(dam_id);
+        let sire = Self::kitty(sire_id);
+
+        ensure!(dam.is_some(), "Invalid dam_id");
+        ensure!(sire.is_some(), "Invalid sire_id");
+        ensure!(dam_id != sire_id, "Needs different parents");
+
+        ensure!(
+            <OwnedKitties<T>>::exists(&(sender.clone(), Some(dam_id))),
+            "Only owner can breed with dam"
+        );
+
+        ensure!(!Self::is_retired(dam_id), "Dam is retired");
+        ensure!(!Self::is_retired(sire_id), "Sire is retired");
+
+        let depth = T::InbreedingDepth::get();
+        let dam_line = Self::ancestors(dam_id, depth);
+        ensure!(
+            !Self::ancestors(sire_id, depth).iter().any(|kitty_id| dam_line.contains(kitty_id)),
+            "Kitties are too closely related"
+        );
+
+        let now = <system::Module<T>>::block_number();
+        ensure!(Self::kitty_cooldown(dam_id).ready_at <= now, "Dam is not ready to breed");
+        ensure!(Self::kitty_cooldown(sire_id).ready_at <= now, "Sire is not ready to breed");
+        ensure!(!<Pregnancies<T>>::exists(dam_id), "Dam is already pregnant");
+        ensure!(!<Pregnancies<T>>::exists(sire_id), "Sire is pregnant");
+
+        if T::GestationPeriod::get().is_zero() {
+            // The child id is taken right away
+            Self::next_kitty_id()?;
+        }
+
+        Ok((dam.unwrap(), sire.unwrap()))
+    }
+
+    /// A kitty and its ancestors up to `generations` back
+    fn ancestors(kitty_id: T::KittyIndex, generations: u32) -> Vec<T::KittyIndex> {
+        let mut ancestors = vec![kitty_id];
+        if generations == 0 {
+            return ancestors;
+        }
+        for (_, matron, sire) in Self::family_tree(kitty_id, generations - 1) {
+            for parent in matron.into_iter().chain(sire) {
+                if !ancestors.contains(&parent) {
+                    ancestors.push(parent);
+                }
+            }
+        }
+        ancestors
+    }
+
+    /// A kitty and its ancestors up to `generations` back, each with its matron and sire, youngest first.
+    /// Lineage comes from `KittyParents`, so burned ancestors are still included.
+    pub fn family_tree(
+        kitty_id: T::KittyIndex,
+        generations: u32,
+    ) -> Vec<(T::KittyIndex, Option<T::KittyIndex>, Option<T::KittyIndex>)> {
+        let mut tree: Vec<(T::KittyIndex, Option<T::KittyIndex>, Option<T::KittyIndex>)> = Vec::new();
+        let mut generation = vec![kitty_id];
+        for depth in 0..=generations {
+            let mut parents = Vec::new();
+            for kitty_id in generation {
+                if tree.iter().any(|(known, _, _)| *known == kitty_id) {
+                    continue;
+                }
+                let kitty_parents = Self::kitty_parents(kitty_id).or_else(|| {
+                    if Self::is_migrated(4, kitty_id) {
+                        return None;
+                    }
+                    Self::kitty(kitty_id).and_then(|kitty| match (kitty.matron, kitty.sire) {
+                        (Some(matron), Some(sire)) => Some((matron, sire)),
+                        _ => None,
+                    })
+                });
+                // Ancestors are known to have existed, only the kitty asked for may be unknown
+                if depth == 0 && kitty_parents.is_none() && !<Kitties<T>>::exists(kitty_id) {
+                    continue;
+                }
+                match kitty_parents {
+                    Some((matron, sire)) => {
+                        tree.push((kitty_id, Some(matron), Some(sire)));
+                        if depth < generations {
+                            parents.push(matron);
+                            parents.push(sire);
+                        }
+                    }
+                    None => tree.push((kitty_id, None, None)),
+                }
+            }
+            if parents.is_empty() {
+                break;
+            }
+            generation = parents;
+        }
+        tree
+    }
+
+    /// Cooldown after the next breeding of a kitty
+    fn cooldown_period(kitty: &KittyOf<T>, cooldown: &Cooldown<T::BlockNumber>) -> T::BlockNumber {
+        let index = cmp::min(kitty.generation / 2 + cooldown.breed_count, MAX_COOLDOWN_INDEX);
+        T::BreedingCooldown::get() * T::BlockNumber::sa(1u64 << index)
+    }
+
+    fn trigger_cooldown(kitty_id: T::KittyIndex, kitty: &KittyOf<T>) {
+        let now = <system::Module<T>>::block_number();
+        <KittyCooldowns<T>>::mutate(kitty_id, |cooldown| {
+            cooldown.ready_at = now + Self::cooldown_period(kitty, cooldown);
+            cooldown.breed_count = cooldown.breed_count.saturating_add(1);
+        });
+
+        if Self::is_retired(kitty_id) {
+            Self::deposit_event(RawEvent::Retired(kitty_id));
+        }
+    }
+
+    /// Times a kitty can breed before it retires
+    pub fn max_breeds() -> u32 {
+        Self::max_breeds_override().unwrap_or_else(T::MaxBreedsPerKitty::get)
+    }
+
+    /// Whether a kitty reached the breeding limit
+    pub fn is_retired(kitty_id: T::KittyIndex) -> bool {
+        Self::kitty_cooldown(kitty_id).breed_count >= Self::max_breeds()
+    }
+
+    fn insert_child(
+        owner: &T::AccountId,
+        kitty_id: T::KittyIndex,
+        matron_id: T::KittyIndex,
+        matron: &KittyOf<T>,
+        pregnancy: &Pregnancy<T::KittyIndex, T::BlockNumber>,
+        selector: [u8; 16],
+    ) {
+        let new_kitty = Kitty {
+            dna: T::Genetics::combine(&matron.dna, &pregnancy.sire_dna, selector),
+            generation: cmp::max(matron.generation, pregnancy.sire_generation).saturating_add(1),
+            matron: Some(matron_id),
+            sire: Some(pregnancy.sire),
+            birth_block: <system::Module<T>>::block_number(),
+            name: Vec::new(),
+        };
+        Self::insert_kitty(owner, kitty_id, new_kitty);
+        <KittyParents<T>>::insert(kitty_id, (matron_id, pregnancy.sire));
+    }
+
+    /// Start a pregnancy, or give birth right away if there is no gestation period.
+    /// A birth is delivered in its due block unless too many are due then, and left for `give_birth` if so.
+    /// Breeding must already be checked with `check_breed`.
+    fn conceive(
+        owner: &T::AccountId,
+        dam_id: T::KittyIndex,
+        dam: &KittyOf<T>,
+        sire_id: T::KittyIndex,
+        sire: &KittyOf<T>,
+    ) -> Result {
+        let gestation = T::GestationPeriod::get();
+        let pregnancy = Pregnancy {
+            sire: sire_id,
+            sire_dna: sire.dna,
+            sire_generation: sire.generation,
+            due: <system::Module<T>>::block_number() + gestation,
+        };
+
+        if gestation.is_zero() {
+            let new_kitty_id = Self::next_kitty_id()?;
+            let selector = Self::random_value(owner);
+
+            Self::trigger_cooldown(dam_id, dam);
+            Self::trigger_cooldown(sire_id, sire);
+            Self::insert_child(owner, new_kitty_id, dam_id, dam, &pregnancy, selector);
+
+            Self::deposit_event(RawEvent::Created(owner.clone(), new_kitty_id));
+        } else {
+            let due = pregnancy.due;
+
+            Self::trigger_cooldown(dam_id, dam);
+            Self::trigger_cooldown(sire_id, sire);
+            <Pregnancies<T>>::insert(dam_id, pregnancy);
+            if Self::has_room(Self::pregnancies_due(due).len()) {
+                <PregnanciesDue<T>>::mutate(due, |matrons| matrons.push(dam_id));
+            }
+
+            Self::deposit_event(RawEvent::Conceived(owner.clone(), dam_id, sire_id, due));
+        }
+        Ok(())
+    }
+
+    fn do_give_birth(matron_id: T::KittyIndex) -> Result {
+        let pregnancy = Self::pregnancy(matron_id);
+        ensure!(pregnancy.is_some(), "Kitty is not pregnant");
+        let pregnancy = pregnancy.unwrap();
+
+        let now = <system::Module<T>>::block_number();
+        ensure!(pregnancy.due <= now, "Kitty is not due yet");
+        // A committed child is only born from its secret. Drawing fresh randomness once the
+        // secret is withheld would let the committer reroll a child they did not like
+        if let Some(key) = Self::committed_pregnancy(matron_id) {
+            ensure!(pregnancy.due + T::RevealDelay::get() < now, "Birth is waiting for the committed secret");
+            Self::forfeit_commitment(key, Some(matron_id));
+            return Ok(());
+        }
+
+        // The matron can not be burned while pregnant, the child goes to her current owner
+        let matron = Self::kitty(matron_id);
+        let owner = Self::kitty_owner(matron_id);
+        ensure!(matron.is_some() && owner.is_some(), "Invalid matron");
+        let (matron, owner) = (matron.unwrap(), owner.unwrap());
+
+        let new_kitty_id = Self::next_kitty_id()?;
+        // An owner at the cap takes delivery through `give_birth` once there is room
+        Self::ensure_can_receive(&owner, 1)?;
+
+        // Randomness of the birth block, unknown when breeding
+        let selector = Self::random_value(&(&owner, matron_id));
+
+        <Pregnancies<T>>::remove(matron_id);
+        Self::insert_child(&owner, new_kitty_id, matron_id, &matron, &pregnancy, selector);
+
+        Self::deposit_event(RawEvent::Born(owner, new_kitty_id, matron_id));
+        Ok(())
+    }
+
+    fn do_breed(
+        sender: &T::AccountId,
+        dam_id: T::KittyIndex,
+        sire_id: T::KittyIndex,
+    ) -> Result {
+        let (dam, sire) = Self::prepare_breed(sender, dam_id, sire_id)?;
+        Self::conceive(sender, dam_id, &dam, sire_id, &sire)
+    }
+
+    /// Check breeding with an owned or approved sire, then charge the fee and use up the approval
+    fn prepare_breed(
+        sender: &T::AccountId,
+        dam_id: T::KittyIndex,
+        sire_id: T::KittyIndex,
+    ) -> result::Result<(KittyOf<T>, KittyOf<T>), &'static str> {
+        let (dam, sire) = Self::check_breed(sender, dam_id, sire_id)?;
+
+        let owns_sire = <OwnedKitties<T>>::exists(&(sender.clone(), Some(sire_id)));
+        ensure!(
+            owns_sire || Self::siring_approval(sire_id).as_ref() == Some(sender),
+            "Not allowed to breed with sire"
+        );
+
+        Self::ensure_can_receive(sender, 1)?;
+        Self::charge_minting_fee(sender, T::BreedingFee::get(), "Not enough balance to pay breeding fee")?;
+
+        if !owns_sire {
+            <SiringApprovals<T>>::remove(sire_id);
+        }
+
+        Ok((dam, sire))
+    }
+
+    /// Check breeding with a sire listed for rent, then pay the siring fee to its owner and charge the breeding fee
+    fn rent_sire(
+        sender: &T::AccountId,
+        dam_id: T::KittyIndex,
+        sire_id: T::KittyIndex,
+        max_fee: BalanceOf<T>,
+    ) -> result::Result<(KittyOf<T>, KittyOf<T>), &'static str> {
+        let sire_owner = Self::kitty_owner(sire_id);
+        ensure!(sire_owner.is_some(), "Invalid sire_id");
+        let sire_owner = sire_owner.unwrap();
+
+        let fee = Self::siring_price(sire_id);
+        ensure!(fee.is_some(), "Sire not for rent");
+        let fee = fee.unwrap();
+
+        ensure!(fee <= max_fee, "Siring fee is too high");
+
+        // Everything is checked before paying so the call either fully succeeds or changes nothing
+        let (dam, sire) = Self::check_breed(sender, dam_id, sire_id)?;
+        Self::ensure_can_receive(sender, 1)?;
+        let breeding_fee = T::BreedingFee::get();
+        let free_balance = T::Currency::free_balance(sender);
+        ensure!(free_balance >= breeding_fee + fee, "Not enough balance to pay breeding and siring fees");
+        // The breeding fee is withdrawn last and must leave the account alive
+        ensure!(
+            breeding_fee.is_zero() || free_balance - breeding_fee - fee >= T::Currency::minimum_balance(),
+            "Not enough balance to pay breeding and siring fees"
+        );
+        ensure!(fee.is_zero() || Self::can_be_paid(&sire_owner, fee), "Sire owner can not be paid");
+
+        if !fee.is_zero() {
+            T::Currency::transfer(sender, &sire_owner, fee)?;
+        }
+        Self::charge_minting_fee(sender, breeding_fee, "Not enough balance to pay breeding fee")?;
+
+        Self::deposit_event(RawEvent::SireRented(sender.clone(), sire_owner, sire_id, fee));
+        Ok((dam, sire))
+    }
+
+    /// Start a pregnancy whose child is born by revealing the secret of `key`.
+    /// The matron stays pregnant until then, but at least for the gestation period.
+    /// Breeding must already be checked and paid for.
+    fn commit_pregnancy(
+        key: (T::AccountId, T::Hash),
+        dam_id: T::KittyIndex,
+        dam: &KittyOf<T>,
+        sire_id: T::KittyIndex,
+        sire: &KittyOf<T>,
+    ) {
+        let reveal_after = <system::Module<T>>::block_number()
+            + cmp::max(T::RevealDelay::get(), T::GestationPeriod::get());
+        let pregnancy = Pregnancy {
+            sire: sire_id,
+            sire_dna: sire.dna,
+            sire_generation: sire.generation,
+            due: reveal_after,
+        };
+
+        Self::trigger_cooldown(dam_id, dam);
+        Self::trigger_cooldown(sire_id, sire);
+        <Pregnancies<T>>::insert(dam_id, pregnancy);
+        <CommittedPregnancies<T>>::insert(dam_id, key.clone());
+        <MintCommitments<T>>::insert(&key, MintCommitment { reveal_after, matron: Some(dam_id) });
+
+        Self::deposit_event(RawEvent::MintCommitted(key.0, key.1, reveal_after));
+    }
+
+    /// Drop a commitment that was not revealed in time, ending the pregnancy of its matron without a child
+    fn forfeit_commitment(key: (T::AccountId, T::Hash), matron: Option<T::KittyIndex>) {
+        <MintCommitments<T>>::remove(&key);
+        if let Some(matron_id) = matron {
+            <CommittedPregnancies<T>>::remove(matron_id);
+            <Pregnancies<T>>::remove(matron_id);
+        }
+
+        Self::deposit_event(RawEvent::MintForfeited(key.0, key.1));
+    }
+
+    fn do_transfer(
+        sender: &T::AccountId,
+        recipient: &T::AccountId,
+        kitty_id: T::KittyIndex,
+    ) -> Result {
+        // Check if the kitty exsit
+        let transfer_kitty = Self::kitty(kitty_id);
+        ensure!(transfer_kitty.is_some(), "Invalid transfer kitty");
+
+        // Check if the sender own this kitty
+        ensure!(
+            <OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))),
+            "Only owner can transfer kitty"
+        );
+        Self::ensure_unlocked(kitty_id)?;
+        if sender != recipient {
+            Self::ensure_can_receive(recipient, 1)?;
+        }
+
+        Self::change_owner(sender, recipient, kitty_id);
+        Ok(())
+    }
+
+    /// Clear listings and approvals made by the current owner
+    fn clear_owner_state(kitty_id: T::KittyIndex) {
+        <KittyPrices<T>>::remove(kitty_id);
+        <SiringPrices<T>>::remove(kitty_id);
+        <SiringApprovals<T>>::remove(kitty_id);
+        <KittyApprovals<T>>::remove(kitty_id);
+    }
+
+    /// Every change of ownership goes through here. Callers must check the kitty can change hands.
+    fn change_owner(from: &T::AccountId, to: &T::AccountId, kitty_id: T::KittyIndex) {
+        Self::clear_owner_state(kitty_id);
+
+        Self::remove_owned_kitty(from, kitty_id);
+        Self::insert_owned_kitty(to, kitty_id);
+        <KittyOwners<T>>::insert(kitty_id, to);
+    }
+
+    fn do_burn(sender: &T::AccountId, kitty_id: T::KittyIndex) -> Result {
+        ensure!(<Kitties<T>>::exists(kitty_id), "Invalid burn kitty");
+        ensure!(
+            <OwnedKitties<T>>::exists(&(sender.clone(), Some(kitty_id))),
+            "Only owner can burn kitty"
+        );
+        ensure!(!<Pregnancies<T>>::exists(kitty_id), "Pregnant kitty can not be burned");
+        Self::ensure_unlocked(kitty_id)?;
+
+        // `KittiesCount` is left untouched so the id is never handed out again
+        Self::clear_owner_state(kitty_id);
+        <Kitties<T>>::remove(kitty_id);
+        <KittyOwners<T>>::remove(kitty_id);
+        <KittyBreeders<T>>::remove(kitty_id);
+        <KittyCooldowns<T>>::remove(kitty_id);
+        Self::remove_owned_kitty(sender, kitty_id);
+
+        for bidder in Self::kitty_offerers(kitty_id) {
+            Self::remove_offer(kitty_id, &bidder);
+        }
+        <KittyOfferers<T>>::remove(kitty_id);
+        for swap_id in Self::kitty_swaps(kitty_id) {
+            if let Some(swap) = Self::swap(swap_id) {
+                Self::remove_swap(swap_id, &swap);
+                Self::deposit_event(RawEvent::SwapCancelled(swap_id));
+            }
+        }
+        <KittySwaps<T>>::remove(kitty_id);
+        Ok(())
+    }
+
+    /// Upgrade storage written by older versions of the module
+    /// Run storage migrations, one step at a time and on at most `MigrationBatchSize` kitties per block.
+    /// Steps see every kitty, including those born while they run.
+    fn migrate() {
+        let version = Self::storage_version();
+        if version >= CURRENT_STORAGE_VERSION {
+            return;
+        }
+
+        let count = Self::kitties_count();
+        let mut kitty_id = Self::migration_cursor();
+        let mut changed = Self::migrated_kitties();
+        for _ in 0..T::MigrationBatchSize::get() {
+            if kitty_id >= count {
+                break;
+            }
+            if Self::migrate_kitty(version, kitty_id) {
+                changed += 1;
+            }
+            kitty_id = kitty_id + One::one();
+        }
+
+        if kitty_id < count {
+            <MigrationCursor<T>>::put(kitty_id);
+            <MigratedKitties<T>>::put(changed);
+            return;
+        }
+
+        <MigrationCursor<T>>::kill();
+        <MigratedKitties<T>>::kill();
+        <StorageVersion<T>>::put(version + 1);
+        Self::deposit_event(match version {
+            0 => RawEvent::KittiesMigrated(changed),
+            1 => RawEvent::PricesPurged(changed),
+            2 => RawEvent::OwnedKittiesCounted(changed),
+            _ => RawEvent::ParentsRecorded(changed),
+        });
+    }
+
+    /// Whether the migration to `version` is done for a kitty
+    fn is_migrated(version: u32, kitty_id: T::KittyIndex) -> bool {
+        let current = Self::storage_version();
+        current >= version || (current + 1 == version && kitty_id < Self::migration_cursor())
+    }
+
+    /// Upgrade a kitty from storage `version` to the next one. Returns whether anything changed
+    fn migrate_kitty(version: u32, kitty_id: T::KittyIndex) -> bool {
+        match version {
+            // Re-encode kitties stored as bare dna in the current format.
+            // Decoding falls back to `Kitty::legacy` for old entries
+            0 => match Self::kitty(kitty_id) {
+                Some(kitty) => {
+                    <Kitties<T>>::insert(kitty_id, kitty);
+                    true
+                }
+                None => false,
+            },
+            // Transfers used to keep the previous owner's price, so an asked price may not be the owner's.
+            // The asker was never stored, so every price set before the fix is dropped and owners ask again.
+            1 => {
+                if !<KittyPrices<T>>::exists(kitty_id) {
+                    return false;
+                }
+                <KittyPrices<T>>::remove(kitty_id);
+                Self::deposit_event(RawEvent::PriceCleared(kitty_id));
+                true
+            }
+            // Fill `OwnedKittiesCount` from the owner of every kitty
+            2 => match Self::kitty_owner(kitty_id) {
+                Some(owner) => {
+                    <OwnedKittiesCount<T>>::mutate(&owner, |count| *count += 1);
+                    true
+                }
+                None => false,
+            },
+            // Fill `KittyParents` from the matron and sire of every existing kitty
+            3 => match Self::kitty(kitty_id) {
+                Some(Kitty { matron: Some(matron), sire: Some(sire), .. }) => {
+                    <KittyParents<T>>::insert(kitty_id, (matron, sire));
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use primitives::{Blake2Hasher, H256};
+    use runtime_io::with_externalities;
+    use runtime_primitives::{
+        testing::{Digest, DigestItem, Header},
+        traits::{BlakeTwo256, IdentityLookup},
+        BuildStorage,
+    };
+    use runtime_primitives::traits::OnFinalize;
+    use support::{assert_noop, assert_ok, impl_outer_origin, parameter_types};
+    use crate::genetics::Mendelian;
+    use crate::randomness::Deterministic;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    // For testing the module, we construct most of a mock runtime. This means
+    // first constructing a configuration type (`Test`) which `impl`s each of the
+    // configuration traits of modules we want to use.
+    #[derive(Clone, Eq, PartialEq, Debug)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl balances::Trait for Test {
+        type Balance = u64;
+        type OnFreeBalanceZero = ();
+        type OnNewAccount = ();
+        type Event = ();
+        type TransactionPayment = ();
+        type DustRemoval = ();
+        type TransferPayment = ();
+    }
+    parameter_types! {
+        pub const BreedingCooldown: u64 = 5;
+        pub const MaxKittiesPerAccount: Option<u32> = Some(5);
+        pub const InbreedingDepth: u32 = 2;
+        pub const MaxBreedsPerKitty: u32 = 3;
+        pub const RevealDelay: u64 = 2;
+        pub const MigrationBatchSize: u32 = 2;
+        pub const MinimumOffer: u64 = 5;
+        pub const MaxOffersPerKitty: u32 = 2;
+        pub const MaxScheduledPerBlock: u32 = 2;
+    }
+    thread_local! {
+        static GESTATION_PERIOD: std::cell::RefCell<u64> = std::cell::RefCell::new(0);
+        static MARKET_FEE: std::cell::RefCell<Permill> = std::cell::RefCell::new(Permill::from_percent(0));
+        static BREEDER_ROYALTY: std::cell::RefCell<Permill> = std::cell::RefCell::new(Permill::from_percent(0));
+        static CREATION_FEE: std::cell::RefCell<u64> = std::cell::RefCell::new(0);
+        static BREEDING_FEE: std::cell::RefCell<u64> = std::cell::RefCell::new(0);
+    }
+    pub struct GestationPeriod;
+    impl Get<u64> for GestationPeriod {
+        fn get() -> u64 {
+            GESTATION_PERIOD.with(|v| *v.borrow())
+        }
+    }
+    pub struct MarketFee;
+    impl Get<Permill> for MarketFee {
+        fn get() -> Permill {
+            MARKET_FEE.with(|v| *v.borrow())
+        }
+    }
+    pub struct BreederRoyalty;
+    impl Get<Permill> for BreederRoyalty {
+        fn get() -> Permill {
+            BREEDER_ROYALTY.with(|v| *v.borrow())
+        }
+    }
+    pub struct CreationFee;
+    impl Get<u64> for CreationFee {
+        fn get() -> u64 {
+            CREATION_FEE.with(|v| *v.borrow())
+        }
+    }
+    pub struct BreedingFee;
+    impl Get<u64> for BreedingFee {
+        fn get() -> u64 {
+            BREEDING_FEE.with(|v| *v.borrow())
+        }
+    }
+    pub struct MutationRate;
+    impl Get<Permill> for MutationRate {
+        fn get() -> Permill {
+            Permill::from_percent(1)
+        }
+    }
+    impl Trait for Test {
+        type Event = ();
+        type KittyIndex = u32;
+        type Currency = balances::Module<Test>;
+        type BreedingCooldown = BreedingCooldown;
+        type GestationPeriod = GestationPeriod;
+        type MarketFee = MarketFee;
+        type MarketFeeDestination = ();
+        type BreederRoyalty = BreederRoyalty;
+        type CreationFee = CreationFee;
+        type BreedingFee = BreedingFee;
+        type MintingFeeDestination = ();
+        type MaxKittiesPerAccount = MaxKittiesPerAccount;
+        type Genetics = Mendelian<MutationRate>;
+        type InbreedingDepth = InbreedingDepth;
+        type MaxBreedsPerKitty = MaxBreedsPerKitty;
+        type RevealDelay = RevealDelay;
+        type MigrationBatchSize = MigrationBatchSize;
+        type MinimumOffer = MinimumOffer;
+        type MaxOffersPerKitty = MaxOffersPerKitty;
+        type MaxScheduledPerBlock = MaxScheduledPerBlock;
+        type Randomness = Deterministic<Test>;
+    }
+    type KittyModule = Module<Test>;
+    type OwnedKittiesTest = OwnedKitties<Test>;
+
+    /// Run `migrate` as in successive blocks until the storage is up to date
+    fn run_migrations() {
+        while KittyModule::storage_version() < CURRENT_STORAGE_VERSION {
+            KittyModule::migrate();
+        }
+    }
+
+    /// Put the parameters tests can change back to their defaults, as tests share a thread
+    fn reset_parameters() {
+        GESTATION_PERIOD.with(|v| *v.borrow_mut() = 0);
+        MARKET_FEE.with(|v| *v.borrow_mut() = Permill::from_percent(0));
+        BREEDER_ROYALTY.with(|v| *v.borrow_mut() = Permill::from_percent(0));
+        CREATION_FEE.with(|v| *v.borrow_mut() = 0);
+        BREEDING_FEE.with(|v| *v.borrow_mut() = 0);
+    }
+
+    // This function basically just builds a genesis storage key/value store according to
+    // our desired mockup.
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        reset_parameters();
+        system::GenesisConfig::<Test>::default()
+            .build_storage()
+            .unwrap()
+            .0
+            .into()
+    }
+
+    fn new_test_ext_with_kitties(kitties: Vec<(u64, [u8; 16])>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        new_test_ext_with_existential_deposit(kitties, 0)
+    }
+
+    fn new_test_ext_with_existential_deposit(
+        kitties: Vec<(u64, [u8; 16])>,
+        existential_deposit: u64,
+    ) -> runtime_io::TestExternalities<Blake2Hasher> {
+        reset_parameters();
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(balances::GenesisConfig::<Test> {
+            balances: vec![(1, 100), (2, 100), (3, 100)],
+            vesting: vec![],
+            transaction_base_fee: 0,
+            transaction_byte_fee: 0,
+            existential_deposit,
+            transfer_fee: 0,
+            creation_fee: 0,
+        }.build_storage().unwrap().0);
+        t.extend(GenesisConfig::<Test> { kitties }.build_storage().unwrap().0);
+        t.into()
+    }
+
+    #[test]
+    fn burn_removes_kitty() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            assert_noop!(KittyModule::burn(Origin::signed(2), 0), "Only owner can burn kitty");
+            assert_ok!(KittyModule::ask(Origin::signed(1), 0, Some(10)));
+
+            assert_ok!(KittyModule::burn(Origin::signed(1), 0));
+
+            assert_eq!(KittyModule::kitty(0), None);
+            assert_eq!(KittyModule::kitty_owner(0), None);
+            assert_eq!(KittyModule::kitty_price(0), None);
+            assert_eq!(OwnedKittiesTest::get(&(1, Some(0))), None);
+            assert_eq!(
+                OwnedKittiesTest::get(&(1, None)),
+                Some(KittyLinkedItem {
+                    prev: Some(1),
+                    next: Some(1),
+                })
+            );
+            // Ids are never reused
+            assert_eq!(KittyModule::kitties_count(), 2);
+            assert_noop!(KittyModule::burn(Origin::signed(1), 0), "Invalid burn kitty");
+        });
+    }
+
+    #[test]
+    fn burn_releases_offers_and_drops_swaps() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16]), (3, [3; 16])]), || {
+            type Balances = balances::Module<Test>;
+
+            assert_ok!(KittyModule::make_offer(Origin::signed(2), 0, 30, 5));
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 0, 20, 6));
+            assert_ok!(KittyModule::propose_swap(Origin::signed(1), 0, 1, None, 5));
+            assert_ok!(KittyModule::propose_swap(Origin::signed(3), 2, 0, None, 5));
+            assert_ok!(KittyModule::propose_swap(Origin::signed(2), 1, 2, None, 5));
+
+            assert_ok!(KittyModule::burn(Origin::signed(1), 0));
+
+            assert_eq!(KittyModule::offer(&(0, 2)), None);
+            assert_eq!(KittyModule::offer(&(0, 3)), None);
+            assert_eq!(KittyModule::kitty_offerers(0), Vec::<u64>::new());
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+
+            assert_eq!(KittyModule::swap(0), None);
+            assert_eq!(KittyModule::swap(1), None);
+            assert!(KittyModule::swap(2).is_some());
+            assert_eq!(KittyModule::kitty_swaps(0), Vec::<SwapId>::new());
+            assert_eq!(KittyModule::kitty_swaps(1), vec![2]);
+            assert_eq!(KittyModule::kitty_swaps(2), vec![2]);
+        });
+    }
+
+    #[test]
+    fn breed_requires_ownership_or_siring_approval() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16]), (2, [3; 16])]), || {
+            assert_noop!(KittyModule::breed(Origin::signed(1), 1, 0), "Only owner can breed with dam");
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 1), "Not allowed to breed with sire");
+
+            assert_noop!(KittyModule::approve_siring(Origin::signed(1), 1, 1), "Only owner can approve siring");
+            assert_ok!(KittyModule::approve_siring(Origin::signed(2), 1, 1));
+            assert_eq!(KittyModule::siring_approval(1), Some(1));
+
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_eq!(KittyModule::kitty_owner(3), Some(1));
+
+            let child = KittyModule::kitty(3).unwrap();
+            assert_eq!(child.generation, 1);
+            assert_eq!(child.matron, Some(0));
+            assert_eq!(child.sire, Some(1));
+
+            // Approval is consumed
+            system::Module::<Test>::set_block_number(100);
+            assert_eq!(KittyModule::siring_approval(1), None);
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 1), "Not allowed to breed with sire");
+
+            // Owner of both parents can always breed
+            assert_ok!(KittyModule::breed(Origin::signed(2), 1, 2));
+        });
+    }
+
+    #[test]
+    fn transfer_clears_siring_approval() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16])]), || {
+            assert_ok!(KittyModule::approve_siring(Origin::signed(2), 1, 1));
+            assert_ok!(KittyModule::transfer(Origin::signed(2), 3, 1));
+
+            assert_eq!(KittyModule::siring_approval(1), None);
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 1), "Not allowed to breed with sire");
+        });
+    }
+
+    #[test]
+    fn breed_with_rented_sire_pays_sire_owner() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16])]), || {
+            assert_noop!(KittyModule::breed_with_rented_sire(Origin::signed(1), 0, 1, 50), "Sire not for rent");
+            assert_noop!(KittyModule::list_sire(Origin::signed(1), 1, Some(30)), "Only owner can list sire");
+
+            assert_ok!(KittyModule::list_sire(Origin::signed(2), 1, Some(30)));
+            assert_noop!(KittyModule::breed_with_rented_sire(Origin::signed(1), 0, 1, 20), "Siring fee is too high");
+            assert_noop!(KittyModule::breed_with_rented_sire(Origin::signed(3), 0, 1, 50), "Only owner can breed with dam");
+
+            assert_ok!(KittyModule::breed_with_rented_sire(Origin::signed(1), 0, 1, 50));
+
+            assert_eq!(KittyModule::kitty_owner(2), Some(1));
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 70);
+            assert_eq!(balances::Module::<Test>::free_balance(&2), 130);
+            // The sire stays listed
+            assert_eq!(KittyModule::siring_price(1), Some(30));
+        });
+    }
+
+    #[test]
+    fn rented_sire_fee_is_checked_before_paying() {
+        // Account 4 owns the sire but has no balance, so it can only be paid at least the existential deposit
+        with_externalities(&mut new_test_ext_with_existential_deposit(vec![(1, [1; 16]), (4, [2; 16])], 10), || {
+            BREEDING_FEE.with(|v| *v.borrow_mut() = 20);
+            assert_ok!(KittyModule::list_sire(Origin::signed(4), 1, Some(5)));
+
+            assert_noop!(KittyModule::breed_with_rented_sire(Origin::signed(1), 0, 1, 5), "Sire owner can not be paid");
+
+            assert_ok!(KittyModule::list_sire(Origin::signed(4), 1, Some(10)));
+            assert_ok!(KittyModule::breed_with_rented_sire(Origin::signed(1), 0, 1, 10));
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 70);
+            assert_eq!(balances::Module::<Test>::free_balance(&4), 10);
+        });
+    }
+
+    #[test]
+    fn legacy_kitties_are_migrated() {
+        // A kitty stored as bare dna, as before metadata was added
+        let legacy = Kitty::<u32, u64>::decode(&mut &[7u8; 16][..]);
+        assert_eq!(legacy, Some(Kitty::legacy([7; 16])));
+
+        let kitty = Kitty::<u32, u64> {
+            generation: 2,
+            matron: Some(3),
+            sire: Some(4),
+            birth_block: 5,
+            name: b"Tom".to_vec(),
+            ..Kitty::legacy([7; 16])
+        };
+        assert_eq!(Kitty::decode(&mut &kitty.encode()[..]), Some(kitty.clone()));
+
+        // Only exactly 16 bytes are a legacy kitty, truncated entries are rejected
+        assert_eq!(Kitty::<u32, u64>::decode(&mut &kitty.encode()[..18]), None);
+        assert_eq!(Kitty::<u32, u64>::decode(&mut &kitty.encode()[..24]), None);
+
+        with_externalities(&mut new_test_ext(), || {
+            let key = runtime_io::blake2_256(&<Kitties<Test>>::key_for(0));
+            runtime_io::set_storage(&key, &[7u8; 16]);
+            <KittiesCount<Test>>::put(1);
+            assert_eq!(KittyModule::storage_version(), 0);
+
+            run_migrations();
+
+            assert_eq!(KittyModule::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(runtime_io::storage(&key), Some(Kitty::<u32, u64>::legacy([7; 16]).encode()));
+            assert_eq!(KittyModule::kitty(0), Some(Kitty::legacy([7; 16])));
+        });
+    }
+
+    #[test]
+    fn breeding_triggers_growing_cooldown() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (1, [3; 16])]), || {
+            system::Module::<Test>::set_block_number(1);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_eq!(KittyModule::kitty_cooldown(0), Cooldown { ready_at: 6, breed_count: 1 });
+            assert_eq!(KittyModule::kitty_cooldown(1), Cooldown { ready_at: 6, breed_count: 1 });
+
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 2), "Dam is not ready to breed");
+            assert_noop!(KittyModule::breed(Origin::signed(1), 2, 1), "Sire is not ready to breed");
+
+            system::Module::<Test>::set_block_number(6);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 2));
+            // Second breeding doubles the cooldown
+            assert_eq!(KittyModule::kitty_cooldown(0), Cooldown { ready_at: 16, breed_count: 2 });
+            assert_eq!(KittyModule::kitty_cooldown(2), Cooldown { ready_at: 11, breed_count: 1 });
+        });
+    }
+
+    #[test]
+    fn pregnant_kitty_can_not_sire() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (1, [3; 16])]), || {
+            GESTATION_PERIOD.with(|v| *v.borrow_mut() = 10);
+            system::Module::<Test>::set_block_number(1);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+
+            // Rested but still pregnant
+            system::Module::<Test>::set_block_number(6);
+            assert_noop!(KittyModule::breed(Origin::signed(1), 2, 0), "Sire is pregnant");
+            assert_ok!(KittyModule::breed(Origin::signed(1), 2, 1));
+        });
+    }
+
+    #[test]
+    fn pregnant_matron_gives_birth_when_due() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (1, [3; 16])]), || {
+            GESTATION_PERIOD.with(|v| *v.borrow_mut() = 3);
+            system::Module::<Test>::set_block_number(1);
+
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_eq!(KittyModule::pregnancy(0).map(|p| (p.sire, p.due)), Some((1, 4)));
+            assert_eq!(KittyModule::pregnancies_due(4), vec![0]);
+            assert_eq!(KittyModule::kitties_count(), 3);
+
+            assert_noop!(KittyModule::give_birth(Origin::signed(2), 0), "Kitty is not due yet");
+            assert_noop!(KittyModule::burn(Origin::signed(1), 0), "Pregnant kitty can not be burned");
+
+            // The child goes to whoever owns the matron at birth
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 0));
+
+            system::Module::<Test>::set_block_number(4);
+            KittyModule::on_finalize(4);
+
+            assert_eq!(KittyModule::pregnancy(0), None);
+            assert_eq!(KittyModule::kitty_owner(3), Some(2));
+            let child = KittyModule::kitty(3).unwrap();
+            assert_eq!(child.matron, Some(0));
+            assert_eq!(child.sire, Some(1));
+            assert_eq!(child.birth_block, 4);
+
+            assert_noop!(KittyModule::give_birth(Origin::signed(2), 0), "Kitty is not pregnant");
+        });
+    }
+
+    #[test]
+    fn dutch_auction_price_decays() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            system::Module::<Test>::set_block_number(10);
+            assert_noop!(
+                KittyModule::create_dutch_auction(Origin::signed(2), 0, 50, 10, 4),
+                "Only owner can auction kitty"
+            );
+            assert_ok!(KittyModule::create_dutch_auction(Origin::signed(1), 0, 50, 10, 4));
+
+            assert_noop!(KittyModule::transfer(Origin::signed(1), 3, 0), "Kitty is in dutch auction");
+            assert_noop!(KittyModule::ask(Origin::signed(1), 0, Some(5)), "Kitty is in dutch auction");
+
+            system::Module::<Test>::set_block_number(11);
+            assert_eq!(KittyModule::dutch_auction_price(&KittyModule::dutch_auction(0).unwrap()), 40);
+            assert_noop!(KittyModule::buy(Origin::signed(2), 0, 35), "Price is too low");
+
+            system::Module::<Test>::set_block_number(12);
+            assert_ok!(KittyModule::buy(Origin::signed(2), 0, 35));
+
+            assert_eq!(KittyModule::kitty_owner(0), Some(2));
+            assert_eq!(KittyModule::dutch_auction(0), None);
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 130);
+            assert_eq!(balances::Module::<Test>::free_balance(&2), 70);
+        });
+    }
+
+    #[test]
+    fn dutch_auction_can_be_cancelled() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            assert_ok!(KittyModule::create_dutch_auction(Origin::signed(1), 0, 50, 10, 4));
+            assert_noop!(KittyModule::cancel_dutch_auction(Origin::signed(2), 0), "Only seller can cancel auction");
+
+            assert_ok!(KittyModule::cancel_dutch_auction(Origin::signed(1), 0));
+
+            assert_noop!(KittyModule::buy(Origin::signed(2), 0, 50), "Kitty not for sale");
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 3, 0));
+        });
+    }
+
+    #[test]
+    fn auction_settles_to_highest_bidder() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            type Balances = balances::Module<Test>;
+
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 0, 20, 5));
+            assert_noop!(KittyModule::transfer(Origin::signed(1), 3, 0), "Kitty is in auction");
+            assert_noop!(KittyModule::bid(Origin::signed(1), 0, 30), "Seller can not bid");
+            assert_noop!(KittyModule::bid(Origin::signed(2), 0, 10), "Bid is below reserve");
+
+            assert_ok!(KittyModule::bid(Origin::signed(2), 0, 30));
+            assert_eq!(Balances::reserved_balance(&2), 30);
+            assert_noop!(KittyModule::bid(Origin::signed(3), 0, 30), "Bid is too low");
+
+            // Outbid, the previous bid is released
+            assert_ok!(KittyModule::bid(Origin::signed(3), 0, 40));
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::reserved_balance(&3), 40);
+
+            // Raising an own bid only reserves the difference
+            assert_ok!(KittyModule::bid(Origin::signed(3), 0, 45));
+            assert_eq!(Balances::reserved_balance(&3), 45);
+            assert_eq!(Balances::free_balance(&3), 55);
+
+            system::Module::<Test>::set_block_number(5);
+            assert_noop!(KittyModule::bid(Origin::signed(2), 0, 50), "Auction has ended");
+            KittyModule::on_finalize(5);
+
+            assert_eq!(KittyModule::auction(0), None);
+            assert_eq!(KittyModule::kitty_owner(0), Some(3));
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Balances::free_balance(&3), 55);
+            assert_eq!(Balances::free_balance(&1), 145);
+        });
+    }
+
+    #[test]
+    fn failed_settlement_releases_the_bid_once() {
+        // Account 4 has no balance, so it can not be paid less than the existential deposit
+        with_externalities(&mut new_test_ext_with_existential_deposit(vec![(4, [1; 16]), (2, [2; 16])], 10), || {
+            type Balances = balances::Module<Test>;
+
+            assert_ok!(KittyModule::start_auction(Origin::signed(4), 0, 5, 5));
+            assert_ok!(KittyModule::bid(Origin::signed(3), 0, 5));
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 1, 30, 10));
+            assert_eq!(Balances::reserved_balance(&3), 35);
+
+            system::Module::<Test>::set_block_number(5);
+            KittyModule::on_finalize(5);
+            assert_eq!(KittyModule::kitty_owner(0), Some(4));
+            assert_eq!(Balances::reserved_balance(&3), 30);
+            assert_eq!(Balances::free_balance(&3), 70);
+
+            // The offer is still backed by its reserve
+            assert_ok!(KittyModule::accept_offer(Origin::signed(2), 1, 3));
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Balances::free_balance(&2), 130);
+        });
+    }
+
+    #[test]
+    fn reserved_sale_pays_without_free_balance() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16])]), || {
+            type Balances = balances::Module<Test>;
+            MARKET_FEE.with(|v| *v.borrow_mut() = Permill::from_percent(10));
+
+            // All of account 3's balance is reserved, so paying out of free balance would fail after the unreserve
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 0, 10, 5));
+            assert_ok!(KittyModule::bid(Origin::signed(3), 0, 60));
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 1, 40, 10));
+            assert_eq!(Balances::free_balance(&3), 0);
+
+            system::Module::<Test>::set_block_number(5);
+            KittyModule::on_finalize(5);
+            assert_eq!(KittyModule::kitty_owner(0), Some(3));
+            assert_eq!(Balances::free_balance(&1), 154);
+            assert_eq!(Balances::reserved_balance(&3), 40);
+
+            assert_ok!(KittyModule::accept_offer(Origin::signed(2), 1, 3));
+            assert_eq!(Balances::free_balance(&2), 136);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+        });
+    }
+
+    #[test]
+    fn offers_can_be_accepted_and_expire() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            type Balances = balances::Module<Test>;
+
+            assert_noop!(KittyModule::make_offer(Origin::signed(1), 0, 10, 5), "Can not make offer for own kitty");
+            assert_ok!(KittyModule::make_offer(Origin::signed(2), 0, 30, 5));
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 0, 20, 3));
+            assert_noop!(KittyModule::make_offer(Origin::signed(3), 0, 25, 3), "Offer already made");
+            assert_eq!(KittyModule::kitty_offerers(0), vec![2, 3]);
+            assert_eq!(Balances::reserved_balance(&3), 20);
+
+            // Expired offers are released
+            system::Module::<Test>::set_block_number(3);
+            assert_noop!(KittyModule::accept_offer(Origin::signed(1), 0, 3), "Offer has expired");
+            KittyModule::on_finalize(3);
+            assert_eq!(KittyModule::offer(&(0, 3)), None);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(KittyModule::kitty_offerers(0), vec![2]);
+
+            assert_noop!(KittyModule::accept_offer(Origin::signed(2), 0, 2), "Only owner can accept offer");
+            assert_ok!(KittyModule::accept_offer(Origin::signed(1), 0, 2));
+
+            assert_eq!(KittyModule::kitty_owner(0), Some(2));
+            assert_eq!(KittyModule::kitty_offerers(0), Vec::<u64>::new());
+            assert_eq!(Balances::reserved_balance(&2), 0);
+            assert_eq!(Balances::free_balance(&2), 70);
+            assert_eq!(Balances::free_balance(&1), 130);
+        });
+    }
+
+    #[test]
+    fn offers_are_limited() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(4, [1; 16]), (4, [2; 16])]), || {
+            assert_noop!(KittyModule::make_offer(Origin::signed(1), 0, 4, 5), "Offer is below minimum");
+            assert_ok!(KittyModule::make_offer(Origin::signed(1), 0, 5, 5));
+            assert_ok!(KittyModule::make_offer(Origin::signed(2), 0, 10, 6));
+            assert_noop!(KittyModule::make_offer(Origin::signed(3), 0, 10, 7), "Kitty has too many offers");
+
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 1, 10, 5));
+            assert_noop!(KittyModule::make_offer(Origin::signed(2), 1, 10, 5), "Too many offers expire in this block");
+            assert_ok!(KittyModule::make_offer(Origin::signed(2), 1, 10, 7));
+            assert_eq!(KittyModule::offers_expiring(5), vec![(0, 1), (1, 3)]);
+        });
+    }
+
+    #[test]
+    fn auction_ends_are_limited_per_block() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (1, [3; 16])]), || {
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 0, 10, 5));
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 1, 10, 5));
+            assert_noop!(KittyModule::start_auction(Origin::signed(1), 2, 10, 5), "Too many auctions end in this block");
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 2, 10, 6));
+            assert_eq!(KittyModule::auctions_ending(5), vec![0, 1]);
+        });
+    }
+
+    #[test]
+    fn births_past_the_block_limit_wait_for_give_birth() {
+        let kitties = vec![(1, [1; 16]), (1, [2; 16]), (2, [3; 16]), (2, [4; 16]), (3, [5; 16]), (3, [6; 16])];
+        with_externalities(&mut new_test_ext_with_kitties(kitties), || {
+            GESTATION_PERIOD.with(|v| *v.borrow_mut() = 3);
+            system::Module::<Test>::set_block_number(1);
+
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_ok!(KittyModule::breed(Origin::signed(2), 2, 3));
+            assert_ok!(KittyModule::breed(Origin::signed(3), 4, 5));
+            assert_eq!(KittyModule::pregnancies_due(4), vec![0, 2]);
+
+            system::Module::<Test>::set_block_number(4);
+            KittyModule::on_finalize(4);
+            assert_eq!(KittyModule::pregnancy(0), None);
+            assert_eq!(KittyModule::pregnancy(2), None);
+            assert!(KittyModule::pregnancy(4).is_some());
+
+            assert_ok!(KittyModule::give_birth(Origin::signed(1), 4));
+            assert_eq!(KittyModule::pregnancy(4), None);
+            assert_eq!(KittyModule::kitties_count(), 9);
+        });
+    }
+
+    #[test]
+    fn offers_can_be_withdrawn() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            assert_ok!(KittyModule::make_offer(Origin::signed(2), 0, 30, 5));
+            assert_ok!(KittyModule::withdraw_offer(Origin::signed(2), 0));
+
+            assert_eq!(balances::Module::<Test>::reserved_balance(&2), 0);
+            assert_noop!(KittyModule::accept_offer(Origin::signed(1), 0, 2), "Offer does not exist");
+            assert_noop!(KittyModule::withdraw_offer(Origin::signed(2), 0), "Offer does not exist");
+        });
+    }
+
+    #[test]
+    fn approved_account_can_transfer_once() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            assert_noop!(KittyModule::transfer_from(Origin::signed(2), 1, 2, 0), "Not allowed to transfer kitty");
+            assert_noop!(KittyModule::approve(Origin::signed(2), 0, Some(2)), "Only owner or operator can approve");
+
+            assert_ok!(KittyModule::approve(Origin::signed(1), 0, Some(2)));
+            assert_eq!(KittyModule::kitty_approval(0), Some(2));
+
+            assert_ok!(KittyModule::transfer_from(Origin::signed(2), 1, 3, 0));
+            assert_eq!(KittyModule::kitty_owner(0), Some(3));
+
+            // Approval is cleared by the ownership change
+            assert_eq!(KittyModule::kitty_approval(0), None);
+            assert_noop!(KittyModule::transfer_from(Origin::signed(2), 3, 2, 0), "Not allowed to transfer kitty");
+        });
+    }
+
+    #[test]
+    fn operator_can_manage_all_kitties() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            assert_ok!(KittyModule::set_approval_for_all(Origin::signed(1), 2, true));
+            assert!(KittyModule::operator_approval(&(1, 2)));
+
+            assert_ok!(KittyModule::approve(Origin::signed(2), 1, Some(3)));
+            assert_ok!(KittyModule::transfer_from(Origin::signed(2), 1, 2, 0));
+            assert_ok!(KittyModule::transfer_from(Origin::signed(3), 1, 3, 1));
+
+            assert_ok!(KittyModule::set_approval_for_all(Origin::signed(1), 2, false));
+            assert!(!KittyModule::operator_approval(&(1, 2)));
+        });
+    }
+
+    #[test]
+    fn transfer_clears_price() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            assert_ok!(KittyModule::ask(Origin::signed(1), 0, Some(10)));
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 0));
+
+            assert_eq!(KittyModule::kitty_price(0), None);
+            assert_noop!(KittyModule::buy(Origin::signed(3), 0, 10), "Kitty not for sale");
+        });
+    }
+
+    #[test]
+    fn migration_purges_old_prices() {
+        with_externalities(&mut new_test_ext(), || {
+            <StorageVersion<Test>>::put(1);
+            <Kitties<Test>>::insert(0, Kitty::legacy([7; 16]));
+            <KittiesCount<Test>>::put(1);
+            <KittyPrices<Test>>::insert(0, 10);
+
+            run_migrations();
+
+            assert_eq!(KittyModule::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(KittyModule::kitty_price(0), None);
+        });
+    }
+
+    #[test]
+    fn swap_exchanges_kitties_and_top_up() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16])]), || {
+            assert_noop!(KittyModule::propose_swap(Origin::signed(1), 1, 0, None, 5), "Only owner can swap kitty");
+            assert_ok!(KittyModule::propose_swap(Origin::signed(1), 0, 1, Some(10), 5));
+
+            assert_noop!(KittyModule::accept_swap(Origin::signed(3), 0), "Only owner can accept swap");
+            assert_noop!(KittyModule::cancel_swap(Origin::signed(2), 0), "Only proposer can cancel swap");
+
+            assert_ok!(KittyModule::accept_swap(Origin::signed(2), 0));
+
+            assert_eq!(KittyModule::kitty_owner(0), Some(2));
+            assert_eq!(KittyModule::kitty_owner(1), Some(1));
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 90);
+            assert_eq!(balances::Module::<Test>::free_balance(&2), 110);
+            assert_eq!(KittyModule::swap(0), None);
+        });
+    }
+
+    #[test]
+    fn expired_swap_can_not_be_accepted() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16])]), || {
+            assert_ok!(KittyModule::propose_swap(Origin::signed(1), 0, 1, None, 5));
+
+            system::Module::<Test>::set_block_number(5);
+            assert_noop!(KittyModule::accept_swap(Origin::signed(2), 0), "Swap has expired");
+
+            // Anyone can clear it once expired
+            assert_ok!(KittyModule::cancel_swap(Origin::signed(3), 0));
+            assert_eq!(KittyModule::swap(0), None);
+        });
+    }
+
+    #[test]
+    fn bundle_is_sold_as_one_lot() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (2, [3; 16])]), || {
+            assert_noop!(KittyModule::ask_bundle(Origin::signed(1), vec![0, 2], 50), "Only owner can sell kitty");
+            assert_noop!(KittyModule::ask_bundle(Origin::signed(1), vec![0, 0], 50), "Kitty is repeated in bundle");
+            assert_ok!(KittyModule::ask_bundle(Origin::signed(1), vec![0, 1], 50));
+
+            assert_noop!(KittyModule::transfer(Origin::signed(1), 3, 1), "Kitty is in bundle");
+            assert_noop!(KittyModule::buy_bundle(Origin::signed(2), 0, 40), "Price is too low");
+
+            assert_ok!(KittyModule::buy_bundle(Origin::signed(2), 0, 50));
+
+            assert_eq!(KittyModule::kitty_owner(0), Some(2));
+            assert_eq!(KittyModule::kitty_owner(1), Some(2));
+            assert_eq!(KittyModule::kitty_bundle(0), None);
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 150);
+            assert_eq!(balances::Module::<Test>::free_balance(&2), 50);
+            assert_ok!(KittyModule::transfer(Origin::signed(2), 3, 1));
+        });
+    }
+
+    #[test]
+    fn sale_pays_market_fee_and_breeder_royalty() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16])]), || {
+            type Balances = balances::Module<Test>;
+            MARKET_FEE.with(|v| *v.borrow_mut() = Permill::from_percent(10));
+            BREEDER_ROYALTY.with(|v| *v.borrow_mut() = Permill::from_percent(5));
+
+            // No royalty when the breeder sells
+            assert_ok!(KittyModule::ask(Origin::signed(1), 0, Some(50)));
+            assert_ok!(KittyModule::buy(Origin::signed(2), 0, 50));
+            assert_eq!(Balances::free_balance(&1), 145);
+            assert_eq!(Balances::free_balance(&2), 50);
+
+            assert_ok!(KittyModule::ask(Origin::signed(2), 0, Some(40)));
+            assert_ok!(KittyModule::buy(Origin::signed(3), 0, 40));
+            assert_eq!(Balances::free_balance(&1), 147);
+            assert_eq!(Balances::free_balance(&2), 84);
+            assert_eq!(Balances::free_balance(&3), 60);
+            assert_eq!(KittyModule::kitty_breeder(0), Some(1));
+        });
+    }
+
+    #[test]
+    fn royalty_of_reaped_breeder_goes_to_seller() {
+        // Account 4 bred the kitty but has no balance, so it can only be paid at least the existential deposit
+        with_externalities(&mut new_test_ext_with_existential_deposit(vec![(4, [1; 16])], 10), || {
+            type Balances = balances::Module<Test>;
+            MARKET_FEE.with(|v| *v.borrow_mut() = Permill::from_percent(10));
+            BREEDER_ROYALTY.with(|v| *v.borrow_mut() = Permill::from_percent(5));
+            assert_ok!(KittyModule::transfer(Origin::signed(4), 1, 0));
+
+            assert_ok!(KittyModule::ask(Origin::signed(1), 0, Some(40)));
+            assert_ok!(KittyModule::buy(Origin::signed(2), 0, 40));
+            assert_eq!(KittyModule::kitty_owner(0), Some(2));
+            assert_eq!(Balances::free_balance(&1), 136);
+            assert_eq!(Balances::free_balance(&2), 60);
+            assert_eq!(Balances::free_balance(&4), 0);
+
+            // Paying from a reserved offer works the same way
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 0, 40, 10));
+            assert_ok!(KittyModule::accept_offer(Origin::signed(2), 0, 3));
+            assert_eq!(KittyModule::kitty_owner(0), Some(3));
+            assert_eq!(Balances::free_balance(&2), 96);
+            assert_eq!(Balances::free_balance(&3), 60);
+            assert_eq!(Balances::reserved_balance(&3), 0);
+            assert_eq!(Balances::free_balance(&4), 0);
+        });
+    }
+
+    #[test]
+    fn create_and_breed_charge_fees() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![]), || {
+            type Balances = balances::Module<Test>;
+            CREATION_FEE.with(|v| *v.borrow_mut() = 30);
+            BREEDING_FEE.with(|v| *v.borrow_mut() = 25);
+
+            assert_ok!(KittyModule::create(Origin::signed(1)));
+            assert_ok!(KittyModule::create(Origin::signed(1)));
+            assert_eq!(Balances::free_balance(&1), 40);
+
+            assert_noop!(KittyModule::create(Origin::signed(4)), "Not enough balance to pay creation fee");
+
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_eq!(Balances::free_balance(&1), 15);
+            assert_eq!(KittyModule::kitties_count(), 3);
+
+            system::Module::<Test>::set_block_number(100);
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 1), "Not enough balance to pay breeding fee");
+        });
+    }
+
+    #[test]
+    fn owned_kitties_are_counted_and_limited() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]); 5]), || {
+            assert_eq!(KittyModule::owned_kitties_count(&1), 5);
+            assert_noop!(KittyModule::create(Origin::signed(1)), "Account owns too many kitties");
+
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 0));
+            assert_eq!(KittyModule::owned_kitties_count(&1), 4);
+            assert_eq!(KittyModule::owned_kitties_count(&2), 1);
+
+            assert_ok!(KittyModule::burn(Origin::signed(2), 0));
+            assert_eq!(KittyModule::owned_kitties_count(&2), 0);
+
+            assert_ok!(KittyModule::create(Origin::signed(1)));
+            assert_eq!(KittyModule::owned_kitties_count(&1), 5);
+            assert_ok!(KittyModule::ask(Origin::signed(1), 1, Some(10)));
+            assert_ok!(KittyModule::buy(Origin::signed(2), 1, 10));
+            assert_eq!(KittyModule::owned_kitties_count(&2), 1);
+        });
+    }
+
+    #[test]
+    fn deliveries_respect_the_ownership_cap() {
+        let kitties = vec![(1, [1; 16]), (1, [2; 16]), (2, [3; 16]), (2, [4; 16]), (2, [5; 16]), (2, [6; 16])];
+        with_externalities(&mut new_test_ext_with_kitties(kitties), || {
+            GESTATION_PERIOD.with(|v| *v.borrow_mut() = 3);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 0));
+            assert_eq!(KittyModule::owned_kitties_count(&2), 5);
+
+            // The child waits until its owner makes room
+            system::Module::<Test>::set_block_number(3);
+            KittyModule::on_finalize(3);
+            assert!(KittyModule::pregnancy(0).is_some());
+            assert_noop!(KittyModule::give_birth(Origin::signed(3), 0), "Account owns too many kitties");
+
+            assert_ok!(KittyModule::burn(Origin::signed(2), 2));
+            assert_ok!(KittyModule::give_birth(Origin::signed(3), 0));
+            assert_eq!(KittyModule::kitty_owner(6), Some(2));
+        });
+
+        let kitties = vec![
+            (1, [1; 16]), (3, [2; 16]), (3, [3; 16]), (3, [4; 16]), (3, [5; 16]), (2, [6; 16]), (1, [7; 16]),
+        ];
+        with_externalities(&mut new_test_ext_with_kitties(kitties), || {
+            assert_ok!(KittyModule::start_auction(Origin::signed(1), 0, 20, 5));
+            assert_ok!(KittyModule::bid(Origin::signed(3), 0, 30));
+            assert_ok!(KittyModule::make_offer(Origin::signed(3), 6, 20, 10));
+            assert_ok!(KittyModule::transfer(Origin::signed(2), 3, 5));
+            assert_noop!(KittyModule::bid(Origin::signed(3), 0, 40), "Account owns too many kitties");
+
+            // The winner filled up before the end, so the kitty stays and only the bid is released
+            system::Module::<Test>::set_block_number(5);
+            KittyModule::on_finalize(5);
+            assert_eq!(KittyModule::auction(0), None);
+            assert_eq!(KittyModule::kitty_owner(0), Some(1));
+            assert_eq!(balances::Module::<Test>::reserved_balance(&3), 20);
+            assert_eq!(balances::Module::<Test>::free_balance(&3), 80);
+            assert_eq!(KittyModule::offer(&(6, 3)).map(|offer| offer.amount), Some(20));
+        });
+    }
+
+    #[test]
+    fn migration_counts_owned_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            <StorageVersion<Test>>::put(2);
+            for (kitty_id, owner) in [1u64, 2, 1].iter().enumerate() {
+                <Kitties<Test>>::insert(kitty_id as u32, Kitty::legacy([7; 16]));
+                <KittyOwners<Test>>::insert(kitty_id as u32, owner);
+            }
+            <KittiesCount<Test>>::put(3);
+
+            // Two kitties are counted per block
+            KittyModule::migrate();
+            assert_eq!(KittyModule::storage_version(), 2);
+            assert_eq!(KittyModule::migration_cursor(), 2);
+            assert_eq!(KittyModule::owned_kitties_count(&1), 1);
+            assert!(KittyModule::is_migrated(3, 1));
+            assert!(!KittyModule::is_migrated(3, 2));
+
+            run_migrations();
+
+            assert_eq!(KittyModule::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(KittyModule::migration_cursor(), 0);
+            assert_eq!(KittyModule::owned_kitties_count(&1), 2);
+            assert_eq!(KittyModule::owned_kitties_count(&2), 1);
+        });
+    }
+
+    #[test]
+    fn close_relatives_can_not_breed() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16]), (1, [3; 16])]), || {
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_noop!(KittyModule::breed(Origin::signed(1), 3, 0), "Kitties are too closely related");
+
+            system::Module::<Test>::set_block_number(100);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert_noop!(KittyModule::breed(Origin::signed(1), 3, 4), "Kitties are too closely related");
+
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 4));
+            assert_ok!(KittyModule::breed(Origin::signed(1), 3, 2));
+
+            assert_eq!(KittyModule::family_tree(5, 1), vec![
+                (5, Some(3), Some(2)),
+                (3, Some(0), Some(1)),
+                (2, None, None),
+            ]);
+            assert_eq!(KittyModule::family_tree(5, 2).len(), 5);
+            assert_eq!(KittyModule::family_tree(6, 2), vec![]);
+
+            // Burning the parents does not hide that 3 and 4 are siblings
+            assert_ok!(KittyModule::burn(Origin::signed(1), 0));
+            assert_ok!(KittyModule::burn(Origin::signed(1), 1));
+            assert_noop!(KittyModule::breed(Origin::signed(1), 3, 4), "Kitties are too closely related");
+            assert_eq!(KittyModule::family_tree(3, 1), vec![
+                (3, Some(0), Some(1)),
+                (0, None, None),
+                (1, None, None),
+            ]);
+        });
+    }
+
+    #[test]
+    fn migration_records_parents() {
+        with_externalities(&mut new_test_ext(), || {
+            <StorageVersion<Test>>::put(3);
+            <Kitties<Test>>::insert(0, Kitty::legacy([7; 16]));
+            <Kitties<Test>>::insert(1, Kitty::legacy([8; 16]));
+            <Kitties<Test>>::insert(2, Kitty { matron: Some(0), sire: Some(1), ..Kitty::legacy([9; 16]) });
+            <KittiesCount<Test>>::put(3);
+
+            run_migrations();
+
+            assert_eq!(KittyModule::kitty_parents(0), None);
+            assert_eq!(KittyModule::kitty_parents(2), Some((0, 1)));
+        });
+    }
+
+    #[test]
+    fn kitties_retire_after_max_breeds() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            for block in 1..4 {
+                system::Module::<Test>::set_block_number(block * 100);
+                assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            }
+            assert!(KittyModule::is_retired(0));
+
+            system::Module::<Test>::set_block_number(1_000);
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 2));
+            assert_noop!(KittyModule::breed(Origin::signed(1), 0, 1), "Dam is retired");
+            assert_noop!(KittyModule::breed(Origin::signed(1), 3, 1), "Sire is retired");
+
+            assert_noop!(KittyModule::set_max_breeds(Origin::signed(1), Some(4)), "bad origin: expected to be a root origin");
+            assert_ok!(KittyModule::set_max_breeds(Origin::ROOT, Some(4)));
+            assert_eq!(KittyModule::max_breeds(), 4);
+            assert_ok!(KittyModule::breed(Origin::signed(1), 0, 1));
+            assert!(KittyModule::is_retired(0));
+
+            assert_ok!(KittyModule::set_max_breeds(Origin::ROOT, None));
+            assert_eq!(KittyModule::max_breeds(), 3);
+        });
+    }
+
+    #[test]
+    fn kitties_are_minted_by_commit_and_reveal() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            let secret = [7u8; 32];
+            let secret_hash = BlakeTwo256::hash(&secret[..]);
+
+            system::Module::<Test>::set_block_number(10);
+            assert_ok!(KittyModule::commit_mint(Origin::signed(1), secret_hash));
+            assert_noop!(KittyModule::commit_mint(Origin::signed(1), secret_hash), "Commitment already exists");
+            assert_noop!(KittyModule::reveal_mint(Origin::signed(1), secret), "Commitment can not be revealed yet");
+
+            system::Module::<Test>::set_block_number(13);
+            assert_noop!(KittyModule::reveal_mint(Origin::signed(1), [8; 32]), "No commitment for this secret");
+            assert_noop!(KittyModule::reveal_mint(Origin::signed(2), secret), "No commitment for this secret");
+            assert_ok!(KittyModule::reveal_mint(Origin::signed(1), secret));
+            assert_eq!(KittyModule::kitty_owner(2), Some(1));
+            assert_eq!(KittyModule::mint_commitment(&(1, secret_hash)), None);
+
+            assert_ok!(KittyModule::commit_breed(Origin::signed(1), 0, 1, secret_hash));
+            assert_eq!(KittyModule::kitty_cooldown(0).breed_count, 1);
+            assert_noop!(KittyModule::burn(Origin::signed(1), 0), "Pregnant kitty can not be burned");
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 0));
+
+            system::Module::<Test>::set_block_number(16);
+            assert_noop!(KittyModule::give_birth(Origin::signed(1), 0), "Birth is waiting for the committed secret");
+            assert_ok!(KittyModule::reveal_mint(Origin::signed(1), secret));
+            let child = KittyModule::kitty(3).unwrap();
+            assert_eq!((child.matron, child.sire), (Some(0), Some(1)));
+            assert_eq!(KittyModule::kitty_owner(3), Some(2));
+            assert_eq!(KittyModule::pregnancy(0), None);
+            assert_eq!(KittyModule::committed_pregnancy(0), None);
+
+            assert_noop!(KittyModule::set_commit_reveal_only(Origin::signed(1), true), "bad origin: expected to be a root origin");
+            assert_ok!(KittyModule::set_commit_reveal_only(Origin::ROOT, true));
+            assert_noop!(KittyModule::create(Origin::signed(1)), "Kitties can only be minted by commit and reveal");
+            assert_noop!(KittyModule::breed(Origin::signed(1), 2, 1), "Kitties can only be minted by commit and reveal");
+            assert_ok!(KittyModule::commit_mint(Origin::signed(1), secret_hash));
+
+            // Renting a sire still works
+            let secret_hash = BlakeTwo256::hash(&[9u8; 32][..]);
+            assert_ok!(KittyModule::list_sire(Origin::signed(1), 1, Some(10)));
+            system::Module::<Test>::set_block_number(30);
+            assert_noop!(
+                KittyModule::breed_with_rented_sire(Origin::signed(2), 0, 1, 10),
+                "Kitties can only be minted by commit and reveal"
+            );
+            assert_ok!(KittyModule::commit_breed_with_rented_sire(Origin::signed(2), 0, 1, 10, secret_hash));
+            assert_eq!(balances::Module::<Test>::free_balance(&1), 110);
+
+            assert_eq!(KittyModule::committed_pregnancy(0), Some((2, secret_hash)));
+        });
+    }
+
+    #[test]
+    fn withheld_secrets_forfeit_the_kitty() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            let secret = [7u8; 32];
+            let secret_hash = BlakeTwo256::hash(&secret[..]);
+
+            system::Module::<Test>::set_block_number(10);
+            assert_ok!(KittyModule::commit_breed(Origin::signed(1), 0, 1, secret_hash));
+
+            // Nobody can deliver the child from other randomness once the secret is withheld
+            system::Module::<Test>::set_block_number(14);
+            assert_noop!(KittyModule::give_birth(Origin::signed(2), 0), "Birth is waiting for the committed secret");
+            system::Module::<Test>::set_block_number(15);
+            assert_ok!(KittyModule::give_birth(Origin::signed(2), 0));
+            assert_eq!(KittyModule::kitties_count(), 2);
+            assert_eq!(KittyModule::pregnancy(0), None);
+            assert_eq!(KittyModule::committed_pregnancy(0), None);
+            assert_eq!(KittyModule::mint_commitment(&(1, secret_hash)), None);
+            assert_noop!(KittyModule::reveal_mint(Origin::signed(1), secret), "No commitment for this secret");
+        });
+    }
+
+    #[test]
+    fn late_reveals_forfeit_the_kitty() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (1, [2; 16])]), || {
+            let secret = [7u8; 32];
+            let secret_hash = BlakeTwo256::hash(&secret[..]);
+            let breed_secret = [8u8; 32];
+            let breed_hash = BlakeTwo256::hash(&breed_secret[..]);
+
+            system::Module::<Test>::set_block_number(10);
+            assert_ok!(KittyModule::commit_mint(Origin::signed(1), secret_hash));
+            assert_ok!(KittyModule::commit_breed(Origin::signed(1), 0, 1, breed_hash));
+
+            // Both are revealable from block 13 to block 14
+            system::Module::<Test>::set_block_number(15);
+            assert_ok!(KittyModule::reveal_mint(Origin::signed(1), secret));
+            assert_ok!(KittyModule::reveal_mint(Origin::signed(1), breed_secret));
+            assert_eq!(KittyModule::kitties_count(), 2);
+            assert_eq!(KittyModule::mint_commitment(&(1, secret_hash)), None);
+            assert_eq!(KittyModule::mint_commitment(&(1, breed_hash)), None);
+            assert_eq!(KittyModule::pregnancy(0), None);
+            assert_eq!(KittyModule::committed_pregnancy(0), None);
+        });
+    }
+
+    #[test]
+    fn kitties_of_pages_through_owned_kitties() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]); 5]), || {
+            assert_ok!(KittyModule::transfer(Origin::signed(1), 2, 1));
+
+            assert_eq!(KittyModule::kitties_of(&1, None, 2), vec![0, 2]);
+            assert_eq!(KittyModule::kitties_of(&1, Some(2), 10), vec![3, 4]);
+            assert_eq!(KittyModule::kitties_of(&1, Some(4), 10), Vec::<u32>::new());
+            assert_eq!(KittyModule::kitties_of(&2, None, 10), vec![1]);
+            assert_eq!(KittyModule::kitties_of(&3, None, 10), Vec::<u32>::new());
+        });
+    }
+
+    #[test]
+    fn kitties_for_sale_lists_prices() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]); 4]), || {
+            assert_ok!(KittyModule::ask(Origin::signed(1), 1, Some(10)));
+            assert_ok!(KittyModule::ask(Origin::signed(1), 3, Some(30)));
+
+            assert_eq!(KittyModule::kitties_for_sale(0, 10), vec![(1, 10), (3, 30)]);
+            assert_eq!(KittyModule::kitties_for_sale(0, 1), vec![(1, 10)]);
+            assert_eq!(KittyModule::kitties_for_sale(2, 10), vec![(3, 30)]);
+        });
+    }
+
+    #[test]
+    fn kitty_traits_are_decoded_from_dna() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [0xff; 16])]), || {
+            assert_eq!(KittyModule::kitty_traits(0), Some(KittyTraits::from_dna(&[0xff; 16])));
+            assert_eq!(KittyModule::kitty_traits(1), None);
+        });
+    }
+
+    #[test]
+    fn genesis_config_creates_kitties() {
+        with_externalities(&mut new_test_ext_with_kitties(vec![(1, [1; 16]), (2, [2; 16]), (1, [3; 16])]), || {
+            assert_eq!(KittyModule::kitties_count(), 3);
+
+            assert_eq!(KittyModule::kitty(0).map(|k| k.dna), Some([1; 16]));
+            assert_eq!(KittyModule::kitty(2).map(|k| k.dna), Some([3; 16]));
+            assert_eq!(KittyModule::kitty_owner(0), Some(1));
+            assert_eq!(KittyModule::kitty_owner(1), Some(2));
+            assert_eq!(KittyModule::kitty_owner(2), Some(1));
+
+            assert_eq!(
+                OwnedKittiesTest::get(&(1, None)),
+                Some(KittyLinkedItem {
+                    prev: Some(2),
+                    next: Some(0),
+                })
+            );
+            assert_eq!(
+                OwnedKittiesTest::get(&(2, Some(1))),
+                Some(KittyLinkedItem {
+                    prev: None,
+                    next: None,
+                })
+            );
+        });
     }
 
     #[test]
@@ -7,7 +7,7 @@
 
 #[cfg(feature = "std")]
 use serde::{Serialize, Deserialize};
-use parity_codec::{Encode, Decode};
+use parity_codec::{Codec, Encode, Decode};
 use rstd::prelude::*;
 #[cfg(feature = "std")]
 use primitives::bytes;
@@ -18,7 +18,7 @@ use runtime_primitives::{
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-	runtime_api, impl_runtime_apis
+	runtime_api, decl_runtime_apis, impl_runtime_apis
 };
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
@@ -32,7 +32,9 @@ pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
 pub use runtime_primitives::{Permill, Perbill};
 pub use timestamp::BlockPeriod;
-pub use support::{StorageValue, construct_runtime};
+pub use support::{StorageValue, construct_runtime, parameter_types, traits::Get};
+pub use genetics::{KittyTraits, Pattern, EyeShape, Rarity};
+pub use kitties::Kitty;
 
 /// The type that is used for identifying authorities.
 pub type AuthorityId = <AuthoritySignature as Verify>::Signer;
@@ -55,13 +57,22 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// Balance of an account.
+pub type Balance = u128;
+
 /// Used for the module template in `./template.rs`
 mod template;
 
 mod kitties;
 
+mod genetics;
+
 mod linked_item;
 
+mod randomness;
+
+mod collective_flip;
+
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
 /// of data like extrinsics, allowing for them to continue syncing the network through upgrades
@@ -172,7 +183,7 @@ impl timestamp::Trait for Runtime {
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
 	type OnFreeBalanceZero = ();
 	/// What to do if a new account is created.
@@ -196,12 +207,82 @@ impl template::Trait for Runtime {
 	type Event = Event;
 }
 
+parameter_types! {
+	/// Ten blocks for a first breeding, growing with generation and breed count.
+	pub const BreedingCooldown: BlockNumber = 10;
+	/// Children are born five blocks after breeding.
+	pub const GestationPeriod: BlockNumber = 5;
+	pub const CreationFee: Balance = 10_000;
+	pub const BreedingFee: Balance = 1_000;
+	pub const MaxKittiesPerAccount: Option<u32> = Some(1_000);
+	/// Kitties sharing a parent or grandparent can not breed.
+	pub const InbreedingDepth: u32 = 2;
+	/// A kitty retires after breeding ten times.
+	pub const MaxBreedsPerKitty: u32 = 10;
+	/// Mint secrets are mixed with the hash of the third block after committing.
+	pub const RevealDelay: BlockNumber = 3;
+	/// Storage migrations go through five hundred kitties per block.
+	pub const MigrationBatchSize: u32 = 500;
+	/// Offers reserve at least a tenth of the breeding fee.
+	pub const MinimumOffer: Balance = 100;
+	/// A kitty takes up to fifty offers at once.
+	pub const MaxOffersPerKitty: u32 = 50;
+	/// A block settles up to a hundred auctions, expires up to a hundred offers and delivers up to a hundred births.
+	pub const MaxScheduledPerBlock: u32 = 100;
+}
+
+/// Two percent of every kitty sale goes to the market.
+pub struct MarketFee;
+impl Get<Permill> for MarketFee {
+	fn get() -> Permill {
+		Permill::from_percent(2)
+	}
+}
+
+/// One percent of every kitty resale goes to its breeder.
+pub struct BreederRoyalty;
+impl Get<Permill> for BreederRoyalty {
+	fn get() -> Permill {
+		Permill::from_percent(1)
+	}
+}
+
+/// One gene in a hundred mutates at birth.
+pub struct MutationRate;
+impl Get<Permill> for MutationRate {
+	fn get() -> Permill {
+		Permill::from_percent(1)
+	}
+}
+
 impl kitties::Trait for Runtime {
 	type Event = Event;
 	type KittyIndex = u32;
 	type Currency = Balances;
+	type BreedingCooldown = BreedingCooldown;
+	type GestationPeriod = GestationPeriod;
+	type MarketFee = MarketFee;
+	/// Market fees are burned.
+	type MarketFeeDestination = ();
+	type BreederRoyalty = BreederRoyalty;
+	type CreationFee = CreationFee;
+	type BreedingFee = BreedingFee;
+	/// Creation and breeding fees are burned.
+	type MintingFeeDestination = ();
+	type MaxKittiesPerAccount = MaxKittiesPerAccount;
+	type Genetics = genetics::Mendelian<MutationRate>;
+	type InbreedingDepth = InbreedingDepth;
+	type MaxBreedsPerKitty = MaxBreedsPerKitty;
+	type RevealDelay = RevealDelay;
+	type MigrationBatchSize = MigrationBatchSize;
+	type MinimumOffer = MinimumOffer;
+	type MaxOffersPerKitty = MaxOffersPerKitty;
+	type MaxScheduledPerBlock = MaxScheduledPerBlock;
+	type Randomness = RandomnessCollectiveFlip;
 }
 
+impl collective_flip::Trait for Runtime {}
+
 construct_runtime!(
 	pub enum Runtime with Log(InternalLog: DigestItem<Hash, AuthorityId, AuthoritySignature>) where
 		Block = Block,
@@ -215,9 +296,10 @@ construct_runtime!(
 		Indices: indices,
 		Balances: balances,
 		Sudo: sudo,
+		RandomnessCollectiveFlip: collective_flip::{Module, Call, Storage},
 		// Used for the module template in `./template.rs`
 		TemplateModule: template::{Module, Call, Storage, Event<T>},
-		Kitties: kitties::{Module, Call, Storage, Event<T>},
+		Kitties: kitties::{Module, Call, Storage, Event<T>, Config<T>},
 	}
 );
 
@@ -238,6 +320,33 @@ pub type CheckedExtrinsic = generic::CheckedExtrinsic<AccountId, Nonce, Call>;
 /// Executive: handles dispatch to the various modules.
 pub type Executive = executive::Executive<Runtime, Block, Context, Balances, AllModules>;
 
+decl_runtime_apis! {
+	/// Kitty queries for clients that do not want to read raw storage.
+	pub trait KittiesApi<AccountId, KittyIndex, Balance, BlockNumber> where
+		AccountId: Codec,
+		KittyIndex: Codec,
+		Balance: Codec,
+		BlockNumber: Codec,
+	{
+		/// A kitty by id.
+		fn kitty(kitty_id: KittyIndex) -> Option<Kitty<KittyIndex, BlockNumber>>;
+		/// Owner of a kitty.
+		fn owner_of(kitty_id: KittyIndex) -> Option<AccountId>;
+		/// Up to `limit` kitties of an account, following `start` or from the first one.
+		fn kitties_of(account: AccountId, start: Option<KittyIndex>, limit: u32) -> Vec<KittyIndex>;
+		/// Price a kitty can be bought for right now.
+		fn price_of(kitty_id: KittyIndex) -> Option<Balance>;
+		/// Up to `limit` kitties for sale with their prices, from kitty id `start` on.
+		fn kitties_for_sale(start: KittyIndex, limit: u32) -> Vec<(KittyIndex, Balance)>;
+		/// Number of kitties ever created, which is also the next kitty id.
+		fn kitties_count() -> KittyIndex;
+		/// Looks of a kitty decoded from its dna.
+		fn kitty_traits(kitty_id: KittyIndex) -> Option<KittyTraits>;
+		/// A kitty and its ancestors up to `generations` back, each with its matron and sire.
+		fn family_tree(kitty_id: KittyIndex, generations: u32) -> Vec<(KittyIndex, Option<KittyIndex>, Option<KittyIndex>)>;
+	}
+}
+
 // Implement our runtime API endpoints. This is just a bunch of proxying.
 impl_runtime_apis! {
 	impl runtime_api::Core<Block> for Runtime {
@@ -309,4 +418,38 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl self::KittiesApi<Block, AccountId, u32, Balance, BlockNumber> for Runtime {
+		fn kitty(kitty_id: u32) -> Option<Kitty<u32, BlockNumber>> {
+			Kitties::kitty(kitty_id)
+		}
+
+		fn owner_of(kitty_id: u32) -> Option<AccountId> {
+			Kitties::kitty_owner(kitty_id)
+		}
+
+		fn kitties_of(account: AccountId, start: Option<u32>, limit: u32) -> Vec<u32> {
+			Kitties::kitties_of(&account, start, limit)
+		}
+
+		fn price_of(kitty_id: u32) -> Option<Balance> {
+			Kitties::price_of(kitty_id)
+		}
+
+		fn kitties_for_sale(start: u32, limit: u32) -> Vec<(u32, Balance)> {
+			Kitties::kitties_for_sale(start, limit)
+		}
+
+		fn kitties_count() -> u32 {
+			Kitties::kitties_count()
+		}
+
+		fn kitty_traits(kitty_id: u32) -> Option<KittyTraits> {
+			Kitties::kitty_traits(kitty_id)
+		}
+
+		fn family_tree(kitty_id: u32, generations: u32) -> Vec<(u32, Option<u32>, Option<u32>)> {
+			Kitties::family_tree(kitty_id, generations)
+		}
+	}
 }
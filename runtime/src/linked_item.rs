@@ -52,6 +52,11 @@ impl<Storage, Key, Value> LinkedList<Storage, Key, Value> where
 		Storage::insert(&(key.clone(), value), item);
 	}
 
+	/// The item after `value`, or the first item when `value` is `None`
+	pub fn next(key: &Key, value: Option<Value>) -> Option<Value> {
+		Self::read(key, value).next
+	}
+
 	pub fn append(key: &Key, value: Value) {
 		let head = Self::read_head(key);
 		let new_head = LinkedItem {
@@ -0,0 +1,27 @@
+use parity_codec::Encode;
+use rstd::marker::PhantomData;
+use runtime_primitives::traits::Hash;
+
+/// A source of randomness for a subject, e.g. the account minting a kitty
+pub trait Randomness<Output> {
+    /// Random value for `subject`. Calls with the same subject in the same block may return the same value
+    fn random(subject: &[u8]) -> Output;
+}
+
+/// The block random seed of the system module, as kitties used before `Randomness` was pluggable
+pub struct SystemSeed<T>(PhantomData<T>);
+
+impl<T: system::Trait> Randomness<T::Hash> for SystemSeed<T> {
+    fn random(subject: &[u8]) -> T::Hash {
+        (<system::Module<T>>::random_seed(), subject).using_encoded(T::Hashing::hash)
+    }
+}
+
+/// Only the hash of the subject. Predictable, so only meant for tests
+pub struct Deterministic<T>(PhantomData<T>);
+
+impl<T: system::Trait> Randomness<T::Hash> for Deterministic<T> {
+    fn random(subject: &[u8]) -> T::Hash {
+        T::Hashing::hash(subject)
+    }
+}
@@ -1,7 +1,7 @@
-use primitives::{ed25519, sr25519, Pair};
+use primitives::{ed25519, sr25519, Pair, blake2_128};
 use substrate_kitties_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig,
+	SudoConfig, IndicesConfig, KittiesConfig,
 };
 use substrate_service;
 
@@ -36,6 +36,13 @@ fn account_key(s: &str) -> AccountId {
 		.public()
 }
 
+/// A few kitties with deterministic dna for each of the given owners.
+fn genesis_kitties(owners: &[AccountId], per_owner: u8) -> Vec<(AccountId, [u8; 16])> {
+	owners.iter()
+		.flat_map(|owner| (0..per_owner).map(move |i| (owner.clone(), blake2_128(&[owner.as_ref(), &[i][..]].concat()))))
+		.collect()
+}
+
 impl Alternative {
 	/// Get an actual chain config from one of the alternatives.
 	pub(crate) fn load(self) -> Result<ChainSpec, String> {
@@ -48,7 +55,8 @@ impl Alternative {
 				], vec![
 					account_key("Alice")
 				],
-					account_key("Alice")
+					account_key("Alice"),
+					genesis_kitties(&[account_key("Alice")], 4),
 				),
 				vec![],
 				None,
@@ -71,6 +79,7 @@ impl Alternative {
 					account_key("Ferdie"),
 				],
 					account_key("Alice"),
+					genesis_kitties(&[account_key("Alice"), account_key("Bob")], 2),
 				),
 				vec![],
 				None,
@@ -90,7 +99,12 @@ impl Alternative {
 	}
 }
 
-fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<AccountId>, root_key: AccountId) -> GenesisConfig {
+fn testnet_genesis(
+	initial_authorities: Vec<AuthorityId>,
+	endowed_accounts: Vec<AccountId>,
+	root_key: AccountId,
+	initial_kitties: Vec<(AccountId, [u8; 16])>,
+) -> GenesisConfig {
 	GenesisConfig {
 		consensus: Some(ConsensusConfig {
 			code: include_bytes!("../runtime/wasm/target/wasm32-unknown-unknown/release/substrate_kitties_runtime_wasm.compact.wasm").to_vec(),
@@ -115,5 +129,8 @@ fn testnet_genesis(initial_authorities: Vec<AuthorityId>, endowed_accounts: Vec<
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		kitties: Some(KittiesConfig {
+			kitties: initial_kitties,
+		}),
 	}
 }
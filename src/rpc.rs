@@ -0,0 +1,250 @@
+//! JSON-RPC servers of full nodes, serving the `kitties_*` methods next to the standard ones.
+//!
+//! substrate-service only serves a fixed set of methods, so full nodes take the RPC addresses
+//! out of the service configuration and start the servers here with one handler for both.
+
+use std::{net::SocketAddr, sync::Arc};
+use futures::{sync::mpsc, Future, Stream};
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use log::info;
+use primitives::{hexdisplay::HexDisplay, H256};
+use runtime_primitives::generic::BlockId;
+use serde::Serialize;
+use substrate_client::runtime_api::ProvideRuntimeApi;
+use substrate_kitties_runtime::{opaque::Block, AccountId, Balance, BlockNumber, KittiesApi, KittyTraits};
+use substrate_rpc_servers::{self as servers, apis};
+use substrate_service::{FactoryFullConfiguration, FullClient, FullComponents, TaskExecutor};
+use crate::service::Factory;
+
+type KittyIndex = u32;
+
+/// Kitties listed when no limit is given
+const DEFAULT_LIMIT: u32 = 20;
+/// Most kitties listed by a single call
+const MAX_LIMIT: u32 = 100;
+
+/// A kitty with its dna in hex and its looks decoded
+#[derive(Serialize)]
+pub struct KittyInfo {
+	pub id: KittyIndex,
+	/// `0x` prefixed hex
+	pub dna: String,
+	pub generation: u32,
+	pub matron: Option<KittyIndex>,
+	pub sire: Option<KittyIndex>,
+	pub birth_block: BlockNumber,
+	pub name: String,
+	pub owner: Option<AccountId>,
+	/// Price it can be bought for right now, as a decimal string since it does not fit a javascript number
+	pub price: Option<String>,
+	pub traits: KittyTraits,
+}
+
+#[rpc]
+pub trait KittiesRpcApi {
+	/// A kitty by id
+	#[rpc(name = "kitties_getKitty")]
+	fn get_kitty(&self, kitty_id: KittyIndex) -> Result<Option<KittyInfo>>;
+
+	/// Up to `limit` kitties of an account, following `start` or from the first one
+	#[rpc(name = "kitties_listOwned")]
+	fn list_owned(&self, account: AccountId, start: Option<KittyIndex>, limit: Option<u32>) -> Result<Vec<KittyInfo>>;
+
+	/// Up to `limit` kitties for sale, from kitty id `start` on
+	#[rpc(name = "kitties_listForSale")]
+	fn list_for_sale(&self, start: Option<KittyIndex>, limit: Option<u32>) -> Result<Vec<KittyInfo>>;
+
+	/// Looks of a kitty decoded from its dna
+	#[rpc(name = "kitties_getTraits")]
+	fn get_traits(&self, kitty_id: KittyIndex) -> Result<Option<KittyTraits>>;
+}
+
+/// Answers the kitties methods from the best block through `KittiesApi`
+pub struct Kitties {
+	client: Arc<FullClient<Factory>>,
+}
+
+impl Kitties {
+	pub fn new(client: Arc<FullClient<Factory>>) -> Self {
+		Kitties { client }
+	}
+
+	fn best_block(&self) -> Result<BlockId<Block>> {
+		let info = self.client.info().map_err(client_error)?;
+		Ok(BlockId::hash(info.chain.best_hash))
+	}
+
+	/// `price` is looked up when not already known
+	fn kitty_info(&self, at: &BlockId<Block>, kitty_id: KittyIndex, price: Option<Balance>) -> Result<Option<KittyInfo>> {
+		let api = self.client.runtime_api();
+		let kitty = match api.kitty(at, kitty_id).map_err(client_error)? {
+			Some(kitty) => kitty,
+			None => return Ok(None),
+		};
+		let owner = api.owner_of(at, kitty_id).map_err(client_error)?;
+		let price = match price {
+			Some(price) => Some(price),
+			None => api.price_of(at, kitty_id).map_err(client_error)?,
+		};
+
+		Ok(Some(KittyInfo {
+			id: kitty_id,
+			dna: format!("0x{}", HexDisplay::from(&kitty.dna)),
+			generation: kitty.generation,
+			matron: kitty.matron,
+			sire: kitty.sire,
+			birth_block: kitty.birth_block,
+			name: String::from_utf8_lossy(&kitty.name).into_owned(),
+			owner,
+			price: price.map(|price| price.to_string()),
+			traits: KittyTraits::from_dna(&kitty.dna),
+		}))
+	}
+}
+
+impl KittiesRpcApi for Kitties {
+	fn get_kitty(&self, kitty_id: KittyIndex) -> Result<Option<KittyInfo>> {
+		self.kitty_info(&self.best_block()?, kitty_id, None)
+	}
+
+	fn list_owned(&self, account: AccountId, start: Option<KittyIndex>, limit: Option<u32>) -> Result<Vec<KittyInfo>> {
+		let at = self.best_block()?;
+		let kitty_ids = self.client.runtime_api()
+			.kitties_of(&at, account, start, page_limit(limit))
+			.map_err(client_error)?;
+
+		let mut kitties = Vec::with_capacity(kitty_ids.len());
+		for kitty_id in kitty_ids {
+			kitties.extend(self.kitty_info(&at, kitty_id, None)?);
+		}
+		Ok(kitties)
+	}
+
+	fn list_for_sale(&self, start: Option<KittyIndex>, limit: Option<u32>) -> Result<Vec<KittyInfo>> {
+		let at = self.best_block()?;
+		let listings = self.client.runtime_api()
+			.kitties_for_sale(&at, start.unwrap_or(0), page_limit(limit))
+			.map_err(client_error)?;
+
+		let mut kitties = Vec::with_capacity(listings.len());
+		for (kitty_id, price) in listings {
+			kitties.extend(self.kitty_info(&at, kitty_id, Some(price))?);
+		}
+		Ok(kitties)
+	}
+
+	fn get_traits(&self, kitty_id: KittyIndex) -> Result<Option<KittyTraits>> {
+		let at = self.best_block()?;
+		self.client.runtime_api().kitty_traits(&at, kitty_id).map_err(client_error)
+	}
+}
+
+fn page_limit(limit: Option<u32>) -> u32 {
+	limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+}
+
+fn client_error<E: std::fmt::Debug>(error: E) -> Error {
+	Error {
+		code: ErrorCode::InternalError,
+		message: "Unable to query kitties".into(),
+		data: Some(format!("{:?}", error).into()),
+	}
+}
+
+/// Addresses the RPC servers listen on, moved out of the service configuration
+#[derive(Default)]
+pub struct RpcConfig {
+	pub http: Option<SocketAddr>,
+	pub ws: Option<SocketAddr>,
+}
+
+impl RpcConfig {
+	/// Take the RPC addresses so the service does not start servers without the kitties methods
+	pub fn take(config: &mut FactoryFullConfiguration<Factory>) -> Self {
+		RpcConfig {
+			http: config.rpc_http.take(),
+			ws: config.rpc_ws.take(),
+		}
+	}
+}
+
+/// Start the HTTP and websocket servers with the standard methods and the kitties methods.
+/// They are stopped when the service exits.
+pub fn start(service: &FullComponents<Factory>, executor: &TaskExecutor) -> substrate_service::error::Result<()> {
+	let config = &service.config;
+	let rpc_config = &config.custom.rpc;
+	let client = service.client();
+
+	// `system_*` requests about the network are answered by the service, as substrate-service does
+	let (system_send_back, system_requests) = mpsc::unbounded();
+	let network = service.network();
+	let should_have_peers = !config.chain_spec.boot_nodes().is_empty();
+	executor.spawn(system_requests.for_each(move |request| {
+		match request {
+			apis::system::Request::Health(sender) => {
+				let _ = sender.send(apis::system::Health {
+					peers: network.peers_debug_info().len(),
+					is_syncing: network.is_major_syncing(),
+					should_have_peers,
+				});
+			}
+			apis::system::Request::Peers(sender) => {
+				let _ = sender.send(network.peers_debug_info().into_iter().map(|(peer_id, peer)| apis::system::PeerInfo {
+					peer_id: peer_id.to_base58(),
+					roles: format!("{:?}", peer.roles),
+					protocol_version: peer.protocol_version,
+					best_hash: peer.best_hash,
+					best_number: peer.best_number,
+				}).collect());
+			}
+			apis::system::Request::NetworkState(sender) => {
+				let _ = sender.send(network.network_state());
+			}
+		}
+		Ok(())
+	}).select(service.on_exit()).then(|_| Ok(())));
+
+	let system_info = apis::system::SystemInfo {
+		impl_name: config.impl_name.into(),
+		impl_version: config.impl_version.into(),
+		chain_name: config.chain_spec.name().into(),
+		properties: config.chain_spec.properties(),
+	};
+	let handler = || {
+		let subscriptions = apis::Subscriptions::new(executor.clone());
+		let chain = apis::chain::Chain::new(client.clone(), subscriptions.clone());
+		let state = apis::state::State::new(client.clone(), subscriptions.clone());
+		let author = apis::author::Author::new(client.clone(), service.transaction_pool(), subscriptions);
+		let system = apis::system::System::new(system_info.clone(), system_send_back.clone());
+
+		let mut handler = servers::rpc_handler::<Block, H256, _, _, _, _>(state, chain, author, system);
+		handler.extend_with(Kitties::new(client.clone()).to_delegate());
+		handler
+	};
+
+	let http = match rpc_config.http {
+		Some(address) => {
+			info!("RPC HTTP server listening on {}", address);
+			let server = servers::start_http(&address, config.rpc_cors.as_ref(), handler())
+				.map_err(|e| format!("Unable to start the RPC HTTP server on {}: {}", address, e))?;
+			Some(server)
+		}
+		None => None,
+	};
+	let ws = match rpc_config.ws {
+		Some(address) => {
+			info!("RPC WS server listening on {}", address);
+			let server = servers::start_ws(&address, config.rpc_cors.as_ref(), handler())
+				.map_err(|e| format!("Unable to start the RPC WS server on {}: {}", address, e))?;
+			Some(server)
+		}
+		None => None,
+	};
+
+	executor.spawn(service.on_exit().then(move |_| {
+		drop((http, ws));
+		Ok(())
+	}));
+	Ok(())
+}
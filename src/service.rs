@@ -19,6 +19,7 @@ use inherents::InherentDataProviders;
 use network::construct_simple_protocol;
 use substrate_executor::native_executor_instance;
 use substrate_service::construct_service_factory;
+use crate::rpc::{self, RpcConfig};
 
 pub use substrate_executor::NativeExecutor;
 // Our native executor instance.
@@ -32,6 +33,8 @@ native_executor_instance!(
 #[derive(Default)]
 pub struct NodeConfig {
 	inherent_data_providers: InherentDataProviders,
+	/// Started by the node rather than the service, to serve the kitties methods
+	rpc: RpcConfig,
 }
 
 construct_simple_protocol! {
@@ -52,9 +55,10 @@ construct_service_factory! {
 		Genesis = GenesisConfig,
 		Configuration = NodeConfig,
 		FullService = FullComponents<Self>
-			{ |config: FactoryFullConfiguration<Self>, executor: TaskExecutor|
+			{ |mut config: FactoryFullConfiguration<Self>, executor: TaskExecutor| {
+				config.custom.rpc = RpcConfig::take(&mut config);
 				FullComponents::<Factory>::new(config, executor)
-			},
+			} },
 		AuthoritySetup = {
 			|service: Self::FullService, executor: TaskExecutor, key: Option<Arc<Pair>>| {
 				if let Some(key) = key {
@@ -78,6 +82,8 @@ construct_service_factory! {
 					)?);
 				}
 
+				rpc::start(&service, &executor)?;
+
 				Ok(service)
 			}
 		},